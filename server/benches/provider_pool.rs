@@ -0,0 +1,84 @@
+//! Throughput of concurrent balance reads through the shared provider pool, next to the
+//! previous pattern of a new provider per call driven by `spawn_blocking` + `block_on`.
+//!
+//! Needs a node behind the chain's first RPC endpoint, e.g. anvil for `local`:
+//!
+//!     BENCH_CHAIN=local cargo bench --bench provider_pool
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use agent_trade::chains::get_chain_info;
+use agent_trade::contracts::eth::eth_balance;
+use alloy::{
+    primitives::Address,
+    providers::{Provider, RootProvider},
+};
+use futures::future::join_all;
+
+/// Tool calls in flight at once.
+const CONCURRENCY: [usize; 4] = [1, 16, 64, 256];
+const ROUNDS: usize = 10;
+
+// How the helpers read a balance before the pool.
+async fn legacy_balance(url: String, account: Address) -> anyhow::Result<()> {
+    tokio::task::spawn_blocking(move || {
+        tokio::runtime::Handle::current().block_on(async {
+            let provider = RootProvider::new_http(url.parse()?);
+            provider.get_balance(account).await?;
+            Ok::<_, anyhow::Error>(())
+        })
+    })
+    .await?
+}
+
+// Run `concurrency` calls as separate tasks, `ROUNDS` times.
+async fn run<F, Fut>(concurrency: usize, call: F) -> Duration
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let tasks = (0..concurrency).map(|_| tokio::spawn(call()));
+        for result in join_all(tasks).await {
+            result.expect("task panicked").expect("balance call failed");
+        }
+    }
+    start.elapsed()
+}
+
+fn calls_per_sec(concurrency: usize, elapsed: Duration) -> f64 {
+    (concurrency * ROUNDS) as f64 / elapsed.as_secs_f64()
+}
+
+#[tokio::main]
+async fn main() {
+    let chain = std::env::var("BENCH_CHAIN").unwrap_or_else(|_| "local".to_string());
+    let url = get_chain_info(&chain)
+        .expect("unknown BENCH_CHAIN")
+        .endpoints()[0]
+        .url
+        .clone();
+    let account = Address::ZERO;
+
+    println!("eth_balance on {} ({} rounds)", chain, ROUNDS);
+    println!(
+        "{:>11} {:>16} {:>16}",
+        "concurrency", "legacy calls/s", "pooled calls/s"
+    );
+    for concurrency in CONCURRENCY {
+        let legacy = run(concurrency, || legacy_balance(url.clone(), account)).await;
+        let pooled = run(concurrency, || {
+            let chain = chain.clone();
+            async move { eth_balance(account, chain).await.map(|_| ()) }
+        })
+        .await;
+        println!(
+            "{:>11} {:>16.0} {:>16.0}",
+            concurrency,
+            calls_per_sec(concurrency, legacy),
+            calls_per_sec(concurrency, pooled)
+        );
+    }
+}
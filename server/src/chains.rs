@@ -6,10 +6,73 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ChainInfo {
     pub chain: String,
-    #[serde(skip_serializing)]
+    /// Single RPC endpoint, used when `rpcs` is empty.
+    #[serde(default, skip_serializing)]
     pub provider_url: String,
+    /// RPC endpoints to fail over between.
+    #[serde(default, skip_serializing)]
+    pub rpcs: Vec<RpcEndpoint>,
     pub tokens: HashMap<String, String>, // token_symbol => token_address
     pub swap_router: String,
+    #[serde(default)]
+    pub uniswap_v3: Option<UniswapV3Info>,
+    #[serde(default)]
+    pub gas: GasPolicy,
+    /// Multicall3 address, for chains where it is not at the usual one.
+    #[serde(default)]
+    pub multicall3: Option<String>,
+}
+
+impl ChainInfo {
+    /// The configured RPC endpoints, falling back to `provider_url`.
+    pub fn endpoints(&self) -> Vec<RpcEndpoint> {
+        if !self.rpcs.is_empty() {
+            return self.rpcs.clone();
+        }
+        vec![RpcEndpoint {
+            url: self.provider_url.clone(),
+            weight: default_weight(),
+        }]
+    }
+}
+
+/// One RPC endpoint of a chain.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RpcEndpoint {
+    pub url: String,
+    /// Share of the calls sent to this endpoint while it is healthy; 0 keeps it as a
+    /// backup that is only used when the others fail.
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+/// Uniswap V3 periphery deployment on a chain.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UniswapV3Info {
+    pub swap_router02: String,
+    pub quoter_v2: String,
+}
+
+/// Fee settings for transactions sent on a chain.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GasPolicy {
+    /// Fee strategy (`slow`, `normal`, `fast` or a percentile like `p75`), overriding
+    /// `GAS_STRATEGY` on this chain.
+    #[serde(default)]
+    pub strategy: Option<String>,
+    /// Highest `maxFeePerGas` the agent may pay, in gwei.
+    #[serde(default)]
+    pub max_fee_gwei: Option<f64>,
+    /// Highest `maxPriorityFeePerGas` the agent may pay, in gwei.
+    #[serde(default)]
+    pub max_priority_fee_gwei: Option<f64>,
+    /// OP-stack chains also charge an L1 data fee for every transaction.
+    #[serde(default)]
+    pub op_stack: bool,
 }
 
 pub static CHAIN_INFOS: Lazy<Vec<ChainInfo>> = Lazy::new(|| {
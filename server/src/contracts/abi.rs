@@ -7,8 +7,221 @@ sol! {
     interface IERC20 {
         function transfer(address to, uint256 amount) public returns (bool);
         function decimals() public view returns (uint8);
+        function symbol() public view returns (string);
+        function name() public view returns (string);
         function allowance(address owner, address spender) external view returns (uint256);
         function approve(address spender, uint256 amount) external returns (bool);
         function balanceOf(address owner) external view returns (uint256);
+
+        // EIP-2612
+        function nonces(address owner) external view returns (uint256);
+        function DOMAIN_SEPARATOR() external view returns (bytes32);
+        function permit(address owner, address spender, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external;
+
+        event Transfer(address indexed from, address indexed to, uint256 value);
+
+        // OpenZeppelin v5 custom errors (ERC-6093), used to decode reverts.
+        error ERC20InsufficientBalance(address sender, uint256 balance, uint256 needed);
+        error ERC20InvalidSender(address sender);
+        error ERC20InvalidReceiver(address receiver);
+        error ERC20InsufficientAllowance(address spender, uint256 allowance, uint256 needed);
+        error ERC20InvalidApprover(address approver);
+        error ERC20InvalidSpender(address spender);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IWETH9 {
+        function deposit() external payable;
+        function withdraw(uint256 wad) external;
+
+        event Deposit(address indexed dst, uint256 wad);
+        event Withdrawal(address indexed src, uint256 wad);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface ISwapRouter02 {
+        struct ExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint24 fee;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        struct ExactInputParams {
+            bytes path;
+            address recipient;
+            uint256 amountIn;
+            uint256 amountOutMinimum;
+        }
+
+        function exactInputSingle(ExactInputSingleParams calldata params) external payable returns (uint256 amountOut);
+        function exactInput(ExactInputParams calldata params) external payable returns (uint256 amountOut);
+        function multicall(uint256 deadline, bytes[] calldata data) external payable returns (bytes[] memory results);
+        function unwrapWETH9(uint256 amountMinimum, address recipient) external payable;
+        function selfPermit(address token, uint256 value, uint256 deadline, uint8 v, bytes32 r, bytes32 s) external payable;
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IQuoterV2 {
+        struct QuoteExactInputSingleParams {
+            address tokenIn;
+            address tokenOut;
+            uint256 amountIn;
+            uint24 fee;
+            uint160 sqrtPriceLimitX96;
+        }
+
+        function quoteExactInputSingle(QuoteExactInputSingleParams memory params) external returns (uint256 amountOut, uint160 sqrtPriceX96After, uint32 initializedTicksCrossed, uint256 gasEstimate);
+        function quoteExactInput(bytes memory path, uint256 amountIn) external returns (uint256 amountOut, uint160[] memory sqrtPriceX96AfterList, uint32[] memory initializedTicksCrossedList, uint256 gasEstimate);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IUniswapV2Factory {
+        function getPair(address tokenA, address tokenB) external view returns (address pair);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IUniswapV2Pair {
+        function token0() external view returns (address);
+        function getReserves() external view returns (uint112 reserve0, uint112 reserve1, uint32 blockTimestampLast);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IPermit2 {
+        struct PermitDetails {
+            address token;
+            uint160 amount;
+            uint48 expiration;
+            uint48 nonce;
+        }
+
+        struct PermitSingle {
+            PermitDetails details;
+            address spender;
+            uint256 sigDeadline;
+        }
+
+        struct PermitBatch {
+            PermitDetails[] details;
+            address spender;
+            uint256 sigDeadline;
+        }
+
+        function allowance(address user, address token, address spender) external view returns (uint160 amount, uint48 expiration, uint48 nonce);
+        function permit(address owner, PermitSingle memory permitSingle, bytes calldata signature) external;
+        function permit(address owner, PermitBatch memory permitBatch, bytes calldata signature) external;
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface ISmartAccount {
+        struct Call {
+            address to;
+            uint256 value;
+            bytes data;
+        }
+
+        // Runs every call from the account, reverting all of them if one fails.
+        function execute(Call[] calldata calls) external payable;
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IEntryPoint {
+        function getNonce(address sender, uint192 key) external view returns (uint256 nonce);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface ISimpleAccountFactory {
+        function createAccount(address owner, uint256 salt) external returns (address ret);
+        function getAddress(address owner, uint256 salt) external view returns (address);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface ISimpleAccount {
+        function execute(address dest, uint256 value, bytes calldata func) external;
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface ISafe {
+        function nonce() external view returns (uint256);
+        function getThreshold() external view returns (uint256);
+        function getOwners() external view returns (address[] memory);
+        function execTransaction(
+            address to,
+            uint256 value,
+            bytes calldata data,
+            uint8 operation,
+            uint256 safeTxGas,
+            uint256 baseGas,
+            uint256 gasPrice,
+            address gasToken,
+            address refundReceiver,
+            bytes memory signatures
+        ) external payable returns (bool success);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IGasPriceOracle {
+        // L1 data fee in wei for an RLP-encoded transaction on OP-stack chains.
+        function getL1Fee(bytes memory data) external view returns (uint256);
+    }
+}
+
+sol! {
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    #[sol(rpc)]
+    interface IMulticall3 {
+        struct Call3 {
+            address target;
+            bool allowFailure;
+            bytes callData;
+        }
+
+        // `Result` in the Multicall3 source, renamed to keep clear of the Rust type.
+        struct CallResult {
+            bool success;
+            bytes returnData;
+        }
+
+        function aggregate3(Call3[] calldata calls) external payable returns (CallResult[] memory returnData);
+        function getEthBalance(address addr) external view returns (uint256 balance);
     }
 }
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, fmt, sync::RwLock};
+
+use alloy::primitives::{
+    utils::{format_units, parse_units, ParseUnits},
+    Address, U256,
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::erc20::decimals_erc20;
+
+/// Decimals never change for a deployed token, so they are fetched once per `(chain, token)`.
+static DECIMALS_CACHE: Lazy<RwLock<HashMap<(String, Address), u8>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Decimals of `token` on `chain_name`; `Address::ZERO` is the native token (18).
+pub async fn token_decimals(token: Address, chain_name: &str) -> Result<u8, anyhow::Error> {
+    if token == Address::ZERO {
+        return Ok(18);
+    }
+    if let Some(decimals) = cached_decimals(token, chain_name) {
+        return Ok(decimals);
+    }
+
+    let decimals = decimals_erc20(token, chain_name.to_string()).await?;
+    cache_decimals(token, chain_name, decimals);
+    Ok(decimals)
+}
+
+/// Decimals of `token` if they were read before.
+pub fn cached_decimals(token: Address, chain_name: &str) -> Option<u8> {
+    if token == Address::ZERO {
+        return Some(18);
+    }
+    DECIMALS_CACHE
+        .read()
+        .unwrap()
+        .get(&(chain_name.to_string(), token))
+        .copied()
+}
+
+/// Remember decimals read some other way, e.g. in a Multicall3 batch.
+pub fn cache_decimals(token: Address, chain_name: &str, decimals: u8) {
+    DECIMALS_CACHE
+        .write()
+        .unwrap()
+        .insert((chain_name.to_string(), token), decimals);
+}
+
+/// An amount of a token in base units, together with the decimals needed to show it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenAmount {
+    pub token: Address,
+    pub raw: U256,
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    pub fn new(token: Address, raw: U256, decimals: u8) -> Self {
+        Self {
+            token,
+            raw,
+            decimals,
+        }
+    }
+
+    /// Parse a human decimal string such as `"0.25"` with known decimals.
+    pub fn parse_with_decimals(
+        token: Address,
+        human: &str,
+        decimals: u8,
+    ) -> Result<Self, anyhow::Error> {
+        let human = human.trim();
+        if human.is_empty() {
+            return Err(anyhow!("empty amount"));
+        }
+        // parse_units silently truncates extra digits; refuse them instead.
+        if let Some((_, frac)) = human.split_once('.') {
+            if frac.len() > decimals as usize {
+                return Err(anyhow!(
+                    "amount {} has more than {} decimals",
+                    human,
+                    decimals
+                ));
+            }
+        }
+        let raw = match parse_units(human, decimals)
+            .map_err(|e| anyhow!("invalid amount {}: {}", human, e))?
+        {
+            ParseUnits::U256(raw) => raw,
+            ParseUnits::I256(_) => return Err(anyhow!("amount must not be negative: {}", human)),
+        };
+        Ok(Self::new(token, raw, decimals))
+    }
+
+    /// Parse a human decimal string, resolving the token's decimals on chain.
+    pub async fn parse(
+        token: Address,
+        human: &str,
+        chain_name: &str,
+    ) -> Result<Self, anyhow::Error> {
+        let decimals = token_decimals(token, chain_name).await?;
+        Self::parse_with_decimals(token, human, decimals)
+    }
+
+    /// Wrap base units read from chain, resolving the token's decimals.
+    pub async fn from_raw(
+        token: Address,
+        raw: U256,
+        chain_name: &str,
+    ) -> Result<Self, anyhow::Error> {
+        let decimals = token_decimals(token, chain_name).await?;
+        Ok(Self::new(token, raw, decimals))
+    }
+
+    /// Human decimal string without trailing zeros, e.g. `"0.25"`.
+    pub fn human(&self) -> String {
+        let formatted =
+            format_units(self.raw, self.decimals).unwrap_or_else(|_| self.raw.to_string());
+        match formatted.split_once('.') {
+            Some((int, frac)) => {
+                let frac = frac.trim_end_matches('0');
+                if frac.is_empty() {
+                    int.to_string()
+                } else {
+                    format!("{}.{}", int, frac)
+                }
+            }
+            None => formatted,
+        }
+    }
+
+    /// Approximate value, for the policy engine's limits.
+    pub fn as_f64(&self) -> f64 {
+        self.human().parse().unwrap_or(f64::MAX)
+    }
+}
+
+impl fmt::Display for TokenAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.human())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_human_amount() {
+        let usdc = Address::repeat_byte(0x01);
+        let amount = TokenAmount::parse_with_decimals(usdc, "10", 6).unwrap();
+        assert_eq!(amount.raw, U256::from(10_000_000u64));
+
+        let amount = TokenAmount::parse_with_decimals(Address::ZERO, "0.25", 18).unwrap();
+        assert_eq!(amount.raw, U256::from(250_000_000_000_000_000u64));
+        assert_eq!(amount.human(), "0.25");
+        assert_eq!(amount.as_f64(), 0.25);
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_amounts() {
+        let token = Address::repeat_byte(0x01);
+        assert!(TokenAmount::parse_with_decimals(token, "", 6).is_err());
+        assert!(TokenAmount::parse_with_decimals(token, "-1", 6).is_err());
+        assert!(TokenAmount::parse_with_decimals(token, "abc", 6).is_err());
+        // More fractional digits than the token supports.
+        assert!(TokenAmount::parse_with_decimals(token, "0.0000001", 6).is_err());
+    }
+
+    #[test]
+    fn test_human_trims_zeros() {
+        let token = Address::repeat_byte(0x01);
+        assert_eq!(
+            TokenAmount::new(token, U256::from(5_000_000u64), 6).human(),
+            "5"
+        );
+        assert_eq!(
+            TokenAmount::new(token, U256::from(1_500_000u64), 6).human(),
+            "1.5"
+        );
+    }
+}
@@ -1,8 +1,250 @@
+use std::{collections::HashMap, env, sync::RwLock};
+
+use super::rpc::ChainRpc;
 use crate::chains::get_chain_info;
-use alloy::providers::RootProvider;
+use crate::wallets::{simulate::SimulationFailure, tracker::Unconfirmed};
+use alloy::{
+    primitives::{Address, U256},
+    providers::RootProvider,
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+pub(crate) fn env_u64(name: &str, default: u64) -> u64 {
+    env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Default slippage tolerance for router swaps, in basis points (0.5%).
+pub static SWAP_SLIPPAGE_BPS: Lazy<u64> = Lazy::new(|| env_u64("SWAP_SLIPPAGE_BPS", 50));
+/// Highest slippage a tool call may ask for.
+pub static MAX_SLIPPAGE_BPS: Lazy<u64> = Lazy::new(|| env_u64("MAX_SLIPPAGE_BPS", 300));
+/// Default number of seconds a router accepts a swap after it was built.
+pub static SWAP_DEADLINE_SECS: Lazy<u64> = Lazy::new(|| env_u64("SWAP_DEADLINE_SECS", 1200));
+/// Bounds on the deadline a tool call may ask for.
+pub static MIN_DEADLINE_SECS: Lazy<u64> = Lazy::new(|| env_u64("MIN_DEADLINE_SECS", 30));
+pub static MAX_DEADLINE_SECS: Lazy<u64> = Lazy::new(|| env_u64("MAX_DEADLINE_SECS", 3600));
+/// Trades whose estimated price impact exceeds this are refused.
+pub static MAX_PRICE_IMPACT_BPS: Lazy<u64> = Lazy::new(|| env_u64("MAX_PRICE_IMPACT_BPS", 300));
+
+/// Slippage and deadline of a swap, after checking the server-side bounds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SwapParams {
+    pub slippage_bps: u64,
+    pub deadline_secs: u64,
+}
+
+impl SwapParams {
+    /// Take the optional tool arguments, falling back to the configured defaults.
+    pub fn new(
+        slippage_bps: Option<u64>,
+        deadline_secs: Option<u64>,
+    ) -> Result<Self, anyhow::Error> {
+        let params = Self {
+            slippage_bps: slippage_bps.unwrap_or(*SWAP_SLIPPAGE_BPS),
+            deadline_secs: deadline_secs.unwrap_or(*SWAP_DEADLINE_SECS),
+        };
+        params.check(*MAX_SLIPPAGE_BPS, *MIN_DEADLINE_SECS, *MAX_DEADLINE_SECS)?;
+        Ok(params)
+    }
+
+    fn check(
+        &self,
+        max_slippage_bps: u64,
+        min_deadline_secs: u64,
+        max_deadline_secs: u64,
+    ) -> Result<(), anyhow::Error> {
+        if self.slippage_bps > max_slippage_bps {
+            return Err(anyhow!(
+                "slippage of {} bps is above the allowed maximum of {} bps",
+                self.slippage_bps,
+                max_slippage_bps
+            ));
+        }
+        if self.deadline_secs < min_deadline_secs || self.deadline_secs > max_deadline_secs {
+            return Err(anyhow!(
+                "deadline of {} seconds is outside the allowed range {}..={}",
+                self.deadline_secs,
+                min_deadline_secs,
+                max_deadline_secs
+            ));
+        }
+        Ok(())
+    }
+
+    /// Unix timestamp `deadline_secs` from now, as routers expect it.
+    pub fn deadline(&self) -> U256 {
+        U256::from(
+            std::time::SystemTime::now()
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .expect("Time error")
+                .as_secs()
+                + self.deadline_secs,
+        )
+    }
+}
+
+/// A swap that could not be quoted or was refused after quoting, reported to the agent.
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("quote on {router} failed: {reason}")]
+pub struct QuoteFailure {
+    /// The router or quoter queried, e.g. `uniswap_v2` or `uniswap_v3`.
+    pub router: String,
+    pub path: Vec<Address>,
+    pub reason: String,
+    /// Estimated price impact in basis points, when the quote succeeded.
+    pub price_impact_bps: Option<u64>,
+}
+
+impl QuoteFailure {
+    pub fn new(router: &str, path: Vec<Address>, reason: impl ToString) -> Self {
+        Self {
+            router: router.to_string(),
+            path,
+            reason: reason.to_string(),
+            price_impact_bps: None,
+        }
+    }
+
+    /// JSON payload handed to the LLM as the tool error.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "error": "quote_failed",
+            "router": self.router,
+            "path": self.path,
+            "reason": self.reason,
+            "price_impact_bps": self.price_impact_bps,
+        })
+        .to_string()
+    }
+}
+
+/// RPC clients shared by every contract helper, one per chain.
+///
+/// A `RootProvider` is a handle to a reference-counted client, so the clones handed out
+/// reuse one HTTP connection pool per endpoint instead of connecting for every call.
+#[derive(Default)]
+pub struct ProviderPool {
+    providers: RwLock<HashMap<String, ChainRpc>>,
+}
+
+impl ProviderPool {
+    /// The provider of `chain_name`, failing over between its endpoints.
+    pub fn get(&self, chain_name: &str) -> std::result::Result<RootProvider, anyhow::Error> {
+        Ok(self.rpc(chain_name)?.provider)
+    }
+
+    /// The client of `chain_name` with the health of its endpoints, connected on first use.
+    pub fn rpc(&self, chain_name: &str) -> std::result::Result<ChainRpc, anyhow::Error> {
+        if let Some(rpc) = self.providers.read().unwrap().get(chain_name) {
+            return Ok(rpc.clone());
+        }
+
+        let chain_info =
+            get_chain_info(chain_name).ok_or_else(|| anyhow!("unknown chain: {}", chain_name))?;
+        let rpc = ChainRpc::connect(&chain_info)?;
+        let mut providers = self.providers.write().unwrap();
+        // Another caller may have connected meanwhile; keep a single client per chain.
+        Ok(providers
+            .entry(chain_name.to_string())
+            .or_insert(rpc)
+            .clone())
+    }
 
+    /// Number of chains connected so far.
+    pub fn len(&self) -> usize {
+        self.providers.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub static PROVIDER_POOL: Lazy<ProviderPool> = Lazy::new(ProviderPool::default);
+
+/// Shared provider of `chain_name` from `PROVIDER_POOL`.
 pub fn make_provider(chain_name: String) -> std::result::Result<RootProvider, anyhow::Error> {
-    let provider_url = get_chain_info(&chain_name).unwrap().provider_url;
-    let provider = RootProvider::new_http(provider_url.parse()?);
-    Ok(provider)
+    PROVIDER_POOL.get(&chain_name)
+}
+
+/// Wrap a send error for the caller, keeping simulation and quote failures intact for the
+/// agent and unconfirmed broadcasts intact for the policy.
+pub fn rpc_error(e: anyhow::Error) -> anyhow::Error {
+    if e.is::<SimulationFailure>() || e.is::<QuoteFailure>() || e.is::<Unconfirmed>() {
+        e
+    } else {
+        anyhow!(format!("alloy rpc error: {}", e))
+    }
+}
+
+/// Lowest acceptable output for `quote` under `slippage_bps`.
+pub fn min_amount_out(quote: U256, slippage_bps: u64) -> U256 {
+    quote * U256::from(10_000 - slippage_bps.min(10_000)) / U256::from(10_000)
+}
+
+fn to_f64(value: U256) -> f64 {
+    value.to_string().parse().unwrap_or(f64::MAX)
+}
+
+/// Estimated price impact of a constant-product route, in basis points.
+///
+/// `reserves` holds `(reserve_in, reserve_out)` for every hop. The spot output applies the
+/// 0.3% LP fee of each hop, so the result only measures the move caused by the trade size.
+pub fn price_impact_bps(amount_in: U256, expected_out: U256, reserves: &[(U256, U256)]) -> u64 {
+    let spot_out = reserves
+        .iter()
+        .fold(to_f64(amount_in), |amount, (reserve_in, reserve_out)| {
+            amount * to_f64(*reserve_out) / to_f64(*reserve_in) * 0.997
+        });
+    if !spot_out.is_finite() || spot_out <= 0.0 {
+        return 10_000;
+    }
+    let impact = 1.0 - to_f64(expected_out) / spot_out;
+    (impact.clamp(0.0, 1.0) * 10_000.0).round() as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_swap_params_bounds() {
+        let params = SwapParams {
+            slippage_bps: 100,
+            deadline_secs: 600,
+        };
+        assert!(params.check(300, 30, 3600).is_ok());
+        assert!(params.check(50, 30, 3600).is_err());
+        assert!(params.check(300, 900, 3600).is_err());
+        assert!(params.check(300, 30, 300).is_err());
+    }
+
+    #[test]
+    fn test_provider_pool() {
+        let pool = ProviderPool::default();
+        assert!(pool.get("unknown").is_err());
+        assert!(pool.is_empty());
+
+        pool.get("local").unwrap();
+        pool.get("local").unwrap();
+        assert_eq!(pool.len(), 1);
+    }
+
+    #[test]
+    fn test_price_impact() {
+        let reserves = [(U256::from(1_000_000u64), U256::from(1_000_000u64))];
+        // Constant product output for 10_000 in with the 0.3% fee.
+        let amount_in = U256::from(10_000u64);
+        let amount_in_with_fee = 10_000u64 * 997;
+        let expected =
+            U256::from(amount_in_with_fee * 1_000_000 / (1_000_000 * 1000 + amount_in_with_fee));
+        assert_eq!(price_impact_bps(amount_in, expected, &reserves), 99);
+
+        // Nothing out for something in counts as a total loss.
+        assert_eq!(price_impact_bps(U256::from(1u64), U256::ZERO, &[]), 10_000);
+    }
 }
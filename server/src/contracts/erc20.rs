@@ -1,38 +1,66 @@
-use crate::wallets::send_tx;
+use crate::wallets::{
+    execute,
+    permit::{erc2612_domain, vrs, Permit},
+    BalanceChange, Execution, TxContext,
+};
 use alloy::{
-    primitives::{Address, TxHash, B256, U256},
+    primitives::{Address, U256},
+    providers::{Provider, RootProvider},
     rpc::types::TransactionRequest,
+    signers::Signature,
+    sol_types::{Eip712Domain, SolCall},
 };
 use anyhow::anyhow;
 
-use super::{abi::IERC20, common::make_provider};
+use super::{
+    abi::IERC20,
+    common::{make_provider, rpc_error},
+    multicall,
+};
+
+/// Request approving `amount` of `token_address` to `spender_address`.
+pub fn approve_erc20_request(
+    token_address: Address,
+    spender_address: Address,
+    amount: U256,
+) -> TransactionRequest {
+    let call = IERC20::approveCall {
+        spender: spender_address,
+        amount,
+    };
+    TransactionRequest::default()
+        .to(token_address)
+        .input(call.abi_encode().into())
+}
+
+/// Request transferring `amount` of `token_address` to `to_address`.
+pub fn transfer_erc20_request(
+    token_address: Address,
+    to_address: Address,
+    amount: U256,
+) -> TransactionRequest {
+    let call = IERC20::transferCall {
+        to: to_address,
+        amount,
+    };
+    TransactionRequest::default()
+        .to(token_address)
+        .input(call.abi_encode().into())
+}
 
 pub async fn approve_erc20(
     spender_address: Address,
-    amount: u128,
+    amount: U256,
     token_address: Address,
     chain_name: String,
-) -> std::result::Result<B256, anyhow::Error> {
-    let tx_hash: std::result::Result<TxHash, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            let result = tokio::runtime::Handle::current().block_on(async {
-                let provider = make_provider(chain_name)?;
-                let erc20 = IERC20::IERC20Instance::new(token_address, provider.clone());
-                let request: TransactionRequest = erc20
-                    .approve(spender_address, U256::from(amount))
-                    .into_transaction_request();
-                send_tx(request, provider).await
-            });
-            result
-        });
-        match handle.await {
-            Ok(Ok(tx)) => Ok(tx.transaction_hash),
-            Ok(Err(e)) => Err(anyhow!(format!("alloy rpc error: {}", e))),
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
-        }
-    }
-    .await;
-    tx_hash
+    account: Option<String>,
+) -> std::result::Result<Execution, anyhow::Error> {
+    let provider = make_provider(chain_name.clone())?;
+    let request = approve_erc20_request(token_address, spender_address, amount);
+    let ctx = TxContext::new(&chain_name, "approve").with_account(account);
+    execute(request, provider, ctx, vec![])
+        .await
+        .map_err(rpc_error)
 }
 
 pub async fn check_allowance_erc20(
@@ -41,27 +69,12 @@ pub async fn check_allowance_erc20(
     token_address: Address,
     chain_name: String,
 ) -> std::result::Result<U256, anyhow::Error> {
-    let allowance: std::result::Result<U256, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            let result = tokio::runtime::Handle::current().block_on(async {
-                let provider = make_provider(chain_name)?;
-                let erc20 = IERC20::IERC20Instance::new(token_address, provider.clone());
-                match erc20.allowance(owner_address, spender_address).call().await {
-                    Ok(allowance) => Ok(allowance._0),
-                    Err(e) => Err(anyhow!(format!("allowance call error: {}", e))),
-                }
-            });
-            result
-        });
-
-        match handle.await {
-            Ok(result) => result,
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
-        }
+    let provider = make_provider(chain_name)?;
+    let erc20 = IERC20::IERC20Instance::new(token_address, provider);
+    match erc20.allowance(owner_address, spender_address).call().await {
+        Ok(allowance) => Ok(allowance._0),
+        Err(e) => Err(anyhow!(format!("allowance call error: {}", e))),
     }
-    .await;
-
-    allowance
 }
 
 pub async fn balance_of_erc20(
@@ -69,65 +82,194 @@ pub async fn balance_of_erc20(
     token_address: Address,
     chain_name: String,
 ) -> std::result::Result<U256, anyhow::Error> {
-    let balance: std::result::Result<U256, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            let result = tokio::runtime::Handle::current().block_on(async {
-                let provider = make_provider(chain_name)?;
-                let erc20 = IERC20::IERC20Instance::new(token_address, provider.clone());
-                match erc20.balanceOf(account_address).call().await {
-                    Ok(balance) => Ok(balance._0),
-                    Err(e) => Err(anyhow!(format!("balanceOf call error: {}", e))),
-                }
-            });
-            result
-        });
-
-        match handle.await {
-            Ok(result) => result,
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
-        }
+    let provider = make_provider(chain_name)?;
+    let erc20 = IERC20::IERC20Instance::new(token_address, provider);
+    match erc20.balanceOf(account_address).call().await {
+        Ok(balance) => Ok(balance._0),
+        Err(e) => Err(anyhow!(format!("balanceOf call error: {}", e))),
     }
-    .await;
+}
+
+/// Balances of `account_address` in each of `tokens`, read in one Multicall3 round trip.
+///
+/// Tokens whose `balanceOf` reverts give `None`.
+pub async fn balances_erc20(
+    account_address: Address,
+    tokens: &[Address],
+    chain_name: String,
+) -> std::result::Result<Vec<Option<U256>>, anyhow::Error> {
+    let call = IERC20::balanceOfCall {
+        owner: account_address,
+    };
+    let calls = tokens
+        .iter()
+        .map(|token| multicall::read(*token, &call))
+        .collect();
+    Ok(multicall::aggregate(&chain_name, calls)
+        .await?
+        .into_iter()
+        .map(|data| multicall::decode::<IERC20::balanceOfCall>(data).map(|balance| balance._0))
+        .collect())
+}
 
-    balance
+/// Allowances of `owner_address` for each `(token, spender)`, read in one Multicall3 round
+/// trip.
+///
+/// Pairs whose `allowance` reverts give `None`.
+pub async fn allowances_erc20(
+    owner_address: Address,
+    pairs: &[(Address, Address)],
+    chain_name: String,
+) -> std::result::Result<Vec<Option<U256>>, anyhow::Error> {
+    let calls = pairs
+        .iter()
+        .map(|(token, spender)| {
+            multicall::read(
+                *token,
+                &IERC20::allowanceCall {
+                    owner: owner_address,
+                    spender: *spender,
+                },
+            )
+        })
+        .collect();
+    Ok(multicall::aggregate(&chain_name, calls)
+        .await?
+        .into_iter()
+        .map(|data| multicall::decode::<IERC20::allowanceCall>(data).map(|allowance| allowance._0))
+        .collect())
+}
+
+pub async fn decimals_erc20(
+    token_address: Address,
+    chain_name: String,
+) -> std::result::Result<u8, anyhow::Error> {
+    let provider = make_provider(chain_name)?;
+    let erc20 = IERC20::IERC20Instance::new(token_address, provider);
+    match erc20.decimals().call().await {
+        Ok(decimals) => Ok(decimals._0),
+        Err(e) => Err(anyhow!(format!("decimals call error: {}", e))),
+    }
+}
+
+/// On-chain `(name, symbol, decimals)` of an ERC20 token.
+pub async fn metadata_erc20(
+    token_address: Address,
+    chain_name: String,
+) -> std::result::Result<(String, String, u8), anyhow::Error> {
+    let provider = make_provider(chain_name)?;
+    let erc20 = IERC20::IERC20Instance::new(token_address, provider);
+    let name = erc20
+        .name()
+        .call()
+        .await
+        .map_err(|e| anyhow!(format!("name call error: {}", e)))?
+        ._0;
+    let symbol = erc20
+        .symbol()
+        .call()
+        .await
+        .map_err(|e| anyhow!(format!("symbol call error: {}", e)))?
+        ._0;
+    let decimals = erc20
+        .decimals()
+        .call()
+        .await
+        .map_err(|e| anyhow!(format!("decimals call error: {}", e)))?
+        ._0;
+    Ok((name, symbol, decimals))
+}
+
+/// What is needed to sign an EIP-2612 `permit` for a token.
+#[derive(Debug, Clone)]
+pub struct PermitSupport {
+    pub domain: Eip712Domain,
+    /// Next permit nonce of the owner.
+    pub nonce: U256,
+}
+
+/// Detect EIP-2612 support of `token_address` for `owner`.
+///
+/// The token must expose `nonces` and a `DOMAIN_SEPARATOR` matching the standard domain
+/// with version "1" or "2". Tokens that don't, or whose calls revert, return `None`.
+/// DAI-style permits pass this check too, so signed permits still go through
+/// `permit_accepted`.
+pub async fn permit_support(
+    provider: &RootProvider,
+    token_address: Address,
+    owner: Address,
+) -> std::result::Result<Option<PermitSupport>, anyhow::Error> {
+    let erc20 = IERC20::IERC20Instance::new(token_address, provider.clone());
+    let Ok(separator) = erc20.DOMAIN_SEPARATOR().call().await else {
+        return Ok(None);
+    };
+    let Ok(nonce) = erc20.nonces(owner).call().await else {
+        return Ok(None);
+    };
+    let Ok(name) = erc20.name().call().await else {
+        return Ok(None);
+    };
+    let name = name._0;
+    let chain_id = provider.get_chain_id().await?;
+
+    Ok(["1", "2"]
+        .into_iter()
+        .map(|version| erc2612_domain(name.clone(), version.to_string(), chain_id, token_address))
+        .find(|domain| domain.separator() == separator._0)
+        .map(|domain| PermitSupport {
+            domain,
+            nonce: nonce._0,
+        }))
+}
+
+/// Whether `token_address` takes `permit` signed as `signature`, checked with `eth_call`.
+///
+/// Tokens with a different `permit` (e.g. DAI's `allowed` flag) revert here, as do owners
+/// that can't sign for themselves with an EOA key, such as smart accounts.
+pub async fn permit_accepted(
+    provider: &RootProvider,
+    token_address: Address,
+    permit: &Permit,
+    signature: &Signature,
+) -> bool {
+    let erc20 = IERC20::IERC20Instance::new(token_address, provider.clone());
+    let (v, r, s) = vrs(signature);
+    erc20
+        .permit(
+            permit.owner,
+            permit.spender,
+            permit.value,
+            permit.deadline,
+            v,
+            r,
+            s,
+        )
+        .call()
+        .await
+        .is_ok()
 }
 
 pub async fn transfer_erc20(
     to_address: Address,
-    amount: u128,
+    amount: U256,
     token_address: Address,
     chain_name: String,
-) -> std::result::Result<B256, anyhow::Error> {
-    // Sync send transfer call.
-    let tx_hash: std::result::Result<TxHash, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            let result = tokio::runtime::Handle::current().block_on(async {
-                // Create a http client to the EVM chain network.
-                let provider = make_provider(chain_name)?;
-                // Create contract instance.
-                let erc20 = IERC20::IERC20Instance::new(token_address, provider.clone());
-                let decimal = erc20.decimals().call().await.unwrap()._0;
-                let request: TransactionRequest = erc20
-                    .transfer(to_address, U256::from(amount * 10u128.pow(decimal.into())))
-                    .into_transaction_request();
-                send_tx(request, provider).await
-            });
-            result
-        });
-        match handle.await {
-            Ok(Ok(tx)) => Ok(tx.transaction_hash),
-            Ok(Err(e)) => Err(anyhow!(format!("alloy rpc error: {}", e))),
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
-        }
-    }
-    .await;
-    tx_hash
+    account: Option<String>,
+) -> std::result::Result<Execution, anyhow::Error> {
+    // Shared client of the chain from the provider pool.
+    let provider = make_provider(chain_name.clone())?;
+    let request = transfer_erc20_request(token_address, to_address, amount);
+    let ctx = TxContext::new(&chain_name, "erc20_transfer").with_account(account);
+    let changes = vec![BalanceChange::outgoing(token_address, amount)];
+    execute(request, provider, ctx, changes)
+        .await
+        .map_err(rpc_error)
 }
 
 #[cfg(test)]
 mod test {
     use crate::contracts::erc20::transfer_erc20;
-    use alloy::primitives::Address;
+    use alloy::primitives::{Address, U256};
     use anyhow::Result;
     use std::str::FromStr;
 
@@ -137,9 +279,10 @@ mod test {
         let token_address = Address::from_str("5FbDB2315678afecb367f032d93F642f64180aa3").unwrap();
         let tx_hash = transfer_erc20(
             to_address,
-            10,
+            U256::from(10),
             token_address,
             String::from("http://localhost:8545"),
+            None,
         )
         .await;
         println!("tx_hash:{}", tx_hash.unwrap());
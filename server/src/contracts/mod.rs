@@ -1,8 +1,13 @@
+pub mod amount;
 pub mod calculator;
 pub mod common;
 pub mod erc20;
 pub mod eth;
 mod gen_tools;
+pub mod multicall;
+pub mod portfolio;
+pub mod rpc;
 pub mod swap;
 pub mod uniswap;
+pub mod weth;
 pub mod abi;
\ No newline at end of file
@@ -0,0 +1,101 @@
+use std::str::FromStr;
+
+use alloy::{
+    primitives::{address, Address, Bytes},
+    sol_types::SolCall,
+};
+use anyhow::anyhow;
+use futures::future::try_join_all;
+
+use super::{abi::IMulticall3, common::make_provider};
+use crate::chains::get_chain_info;
+
+/// Where Multicall3 is deployed on most EVM chains.
+pub const MULTICALL3: Address = address!("cA11bde05977b3631167028862bE2a173976CA11");
+
+/// Calls per `aggregate3`, keeping each `eth_call` under the gas and size limits of nodes.
+const MAX_BATCH: usize = 500;
+
+/// Multicall3 of `chain_name`: the configured `multicall3`, else `MULTICALL3`.
+pub fn multicall_address(chain_name: &str) -> Result<Address, anyhow::Error> {
+    let chain_info =
+        get_chain_info(chain_name).ok_or_else(|| anyhow!("unknown chain: {}", chain_name))?;
+    match chain_info.multicall3 {
+        Some(address) => Address::from_str(&address)
+            .map_err(|e| anyhow!("invalid multicall3 of {}: {}", chain_name, e)),
+        None => Ok(MULTICALL3),
+    }
+}
+
+/// A read of `call` on `target`, allowed to fail without failing the batch.
+pub fn read<C: SolCall>(target: Address, call: &C) -> IMulticall3::Call3 {
+    IMulticall3::Call3 {
+        target,
+        allowFailure: true,
+        callData: call.abi_encode().into(),
+    }
+}
+
+/// Decode what a `read` returned; failed calls and undecodable data give `None`.
+pub fn decode<C: SolCall>(data: Option<Bytes>) -> Option<C::Return> {
+    data.and_then(|data| C::abi_decode_returns(&data, true).ok())
+}
+
+/// Run `calls` through Multicall3 on `chain_name`, one `eth_call` per `MAX_BATCH` calls.
+///
+/// Returns the data of every call in order, `None` for those that reverted.
+pub async fn aggregate(
+    chain_name: &str,
+    calls: Vec<IMulticall3::Call3>,
+) -> Result<Vec<Option<Bytes>>, anyhow::Error> {
+    let address = multicall_address(chain_name)?;
+    let provider = make_provider(chain_name.to_string())?;
+    let multicall = IMulticall3::IMulticall3Instance::new(address, provider);
+
+    let batches = try_join_all(calls.chunks(MAX_BATCH).map(|batch| {
+        let call = multicall.aggregate3(batch.to_vec());
+        async move { call.call().await }
+    }))
+    .await
+    .map_err(|e| {
+        anyhow!(format!(
+            "aggregate3 call error (is Multicall3 deployed at {} on {}?): {}",
+            address, chain_name, e
+        ))
+    })?;
+    Ok(batches
+        .into_iter()
+        .flat_map(|batch| batch.returnData)
+        .map(|result| result.success.then_some(result.returnData))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::contracts::abi::IERC20;
+    use alloy::{primitives::U256, sol_types::SolValue};
+
+    #[test]
+    fn test_read_and_decode() {
+        let token = Address::repeat_byte(0x01);
+        let owner = Address::repeat_byte(0x02);
+        let call = read(token, &IERC20::balanceOfCall { owner });
+        assert_eq!(call.target, token);
+        assert!(call.allowFailure);
+        assert_eq!(
+            IERC20::balanceOfCall::abi_decode(&call.callData, true)
+                .unwrap()
+                .owner,
+            owner
+        );
+
+        let data = Bytes::from(U256::from(42u64).abi_encode());
+        let balance = decode::<IERC20::balanceOfCall>(Some(data)).unwrap();
+        assert_eq!(balance._0, U256::from(42u64));
+
+        // Reverted calls and calls to accounts without code.
+        assert!(decode::<IERC20::balanceOfCall>(None).is_none());
+        assert!(decode::<IERC20::balanceOfCall>(Some(Bytes::new())).is_none());
+    }
+}
@@ -0,0 +1,135 @@
+use alloy::primitives::{Address, Bytes, U256};
+use anyhow::anyhow;
+use serde::Serialize;
+
+use super::{
+    abi::{IMulticall3, IERC20},
+    amount::{cache_decimals, cached_decimals, TokenAmount},
+    multicall,
+};
+use crate::service::token::registry;
+
+/// A balance of an account, in whole tokens.
+#[derive(Debug, Clone, Serialize)]
+pub struct Holding {
+    pub symbol: String,
+    /// `Address::ZERO` for the native coin.
+    pub token: Address,
+    pub balance: String,
+    pub raw: U256,
+}
+
+/// Native and configured token balances of `account` on `chain_name`.
+///
+/// Balances and the decimals not cached yet are read in a single Multicall3 round trip.
+/// Tokens whose calls revert are left out.
+pub async fn portfolio(
+    account: Address,
+    chain_name: &str,
+) -> std::result::Result<Vec<Holding>, anyhow::Error> {
+    let mut tokens = registry::configured(chain_name);
+    tokens.sort();
+    let unknown_decimals: Vec<Address> = tokens
+        .iter()
+        .map(|(_, token)| *token)
+        .filter(|token| cached_decimals(*token, chain_name).is_none())
+        .collect();
+
+    let balance_of = IERC20::balanceOfCall { owner: account };
+    let mut calls = vec![multicall::read(
+        multicall::multicall_address(chain_name)?,
+        &IMulticall3::getEthBalanceCall { addr: account },
+    )];
+    calls.extend(
+        tokens
+            .iter()
+            .map(|(_, token)| multicall::read(*token, &balance_of)),
+    );
+    calls.extend(
+        unknown_decimals
+            .iter()
+            .map(|token| multicall::read(*token, &IERC20::decimalsCall {})),
+    );
+
+    let results = multicall::aggregate(chain_name, calls).await?;
+    let (native, balances, decimals) = split_results(results, tokens.len());
+    let native = native.ok_or_else(|| anyhow!("getEthBalance call error on {}", chain_name))?;
+    for (token, decimals) in unknown_decimals.into_iter().zip(decimals) {
+        if let Some(decimals) = decimals {
+            cache_decimals(token, chain_name, decimals);
+        }
+    }
+
+    let mut holdings = vec![Holding {
+        symbol: "ETH".to_string(),
+        token: Address::ZERO,
+        balance: TokenAmount::new(Address::ZERO, native, 18).to_string(),
+        raw: native,
+    }];
+    for ((symbol, token), balance) in tokens.into_iter().zip(balances) {
+        let (Some(raw), Some(decimals)) = (balance, cached_decimals(token, chain_name)) else {
+            log::warn!("balance of {} on {} unavailable", symbol, chain_name);
+            continue;
+        };
+        holdings.push(Holding {
+            symbol,
+            token,
+            balance: TokenAmount::new(token, raw, decimals).to_string(),
+            raw,
+        });
+    }
+    Ok(holdings)
+}
+
+/// Split the results of `portfolio`'s batch: the native balance first, then the balance
+/// of each of the `tokens` tokens, then the decimals that were not cached.
+fn split_results(
+    results: Vec<Option<Bytes>>,
+    tokens: usize,
+) -> (Option<U256>, Vec<Option<U256>>, Vec<Option<u8>>) {
+    let mut results = results.into_iter();
+    let native = multicall::decode::<IMulticall3::getEthBalanceCall>(results.next().flatten())
+        .map(|native| native.balance);
+    let balances = results
+        .by_ref()
+        .take(tokens)
+        .map(|data| multicall::decode::<IERC20::balanceOfCall>(data).map(|balance| balance._0))
+        .collect();
+    let decimals = results
+        .map(|data| multicall::decode::<IERC20::decimalsCall>(data).map(|decimals| decimals._0))
+        .collect();
+    (native, balances, decimals)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy::sol_types::SolValue;
+
+    #[test]
+    fn test_split_results() {
+        let word = |value: u64| Some(Bytes::from(U256::from(value).abi_encode()));
+        let results = vec![
+            word(7),
+            word(100),
+            None,
+            word(200),
+            word(6),
+            Some(Bytes::new()),
+        ];
+
+        let (native, balances, decimals) = split_results(results, 3);
+        assert_eq!(native, Some(U256::from(7)));
+        assert_eq!(
+            balances,
+            vec![Some(U256::from(100)), None, Some(U256::from(200))]
+        );
+        // Calls to accounts without code return no data.
+        assert_eq!(decimals, vec![Some(6), None]);
+
+        let (native, balances, decimals) = split_results(vec![None], 0);
+        assert_eq!(native, None);
+        assert!(balances.is_empty());
+        assert!(decimals.is_empty());
+    }
+}
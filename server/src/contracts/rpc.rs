@@ -0,0 +1,534 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use alloy::{
+    providers::{Provider, RootProvider},
+    rpc::{
+        client::RpcClient,
+        json_rpc::{RequestPacket, ResponsePacket},
+    },
+    transports::{
+        http::{
+            reqwest::{Client, Url},
+            Http,
+        },
+        TransportError, TransportErrorKind, TransportFut,
+    },
+};
+use anyhow::anyhow;
+use futures::future::join_all;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use tower::Service;
+
+use super::common::{env_u64, PROVIDER_POOL};
+use crate::chains::{ChainInfo, RpcEndpoint, CHAIN_INFOS};
+
+/// Blocks an endpoint may trail the best one of its chain before it is taken out.
+pub static MAX_BLOCK_LAG: Lazy<u64> = Lazy::new(|| env_u64("RPC_MAX_BLOCK_LAG", 5));
+/// How long a health probe waits for an answer.
+pub static MAX_LATENCY: Lazy<Duration> =
+    Lazy::new(|| Duration::from_millis(env_u64("RPC_MAX_LATENCY_MS", 3000)));
+/// How often every endpoint is probed.
+pub static PROBE_INTERVAL: Lazy<Duration> =
+    Lazy::new(|| Duration::from_secs(env_u64("RPC_PROBE_INTERVAL_SECS", 30)));
+/// Passes over the endpoints after the first one fails entirely.
+pub static MAX_RETRIES: Lazy<u64> = Lazy::new(|| env_u64("RPC_MAX_RETRIES", 3));
+/// Pause before the first retry pass, doubled for every later one.
+pub static RETRY_BACKOFF: Lazy<Duration> =
+    Lazy::new(|| Duration::from_millis(env_u64("RPC_RETRY_BACKOFF_MS", 200)));
+
+/// Failed calls in a row after which an endpoint is taken out until it answers again.
+const FAILURE_THRESHOLD: u32 = 3;
+
+tokio::task_local! {
+    /// Endpoint the calls of each chain stick to within a `pinned` operation.
+    static PINNED: Mutex<HashMap<String, usize>>;
+}
+
+/// Run `f` with the calls of each chain pinned to a single endpoint, so that the nonce,
+/// allowances and state it reads all come from the same node. Nested operations share
+/// the pins of the outer one.
+pub async fn pinned<F: Future>(f: F) -> F::Output {
+    if PINNED.try_with(|_| ()).is_ok() {
+        f.await
+    } else {
+        PINNED.scope(Mutex::new(HashMap::new()), f).await
+    }
+}
+
+/// Whether `request` broadcasts a transaction (`eth_sendRawTransaction` and its
+/// `Conditional` variants).
+fn is_send(request: &RequestPacket) -> bool {
+    let requests = match request {
+        RequestPacket::Single(request) => std::slice::from_ref(request),
+        RequestPacket::Batch(requests) => requests.as_slice(),
+    };
+    requests
+        .iter()
+        .any(|request| request.method().starts_with("eth_sendRawTransaction"))
+}
+
+/// Health of one endpoint, as shown by `/chains/status`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EndpointStatus {
+    /// Scheme and host only; paths and queries often carry API keys.
+    pub url: String,
+    pub weight: u32,
+    pub healthy: bool,
+    pub block_number: Option<u64>,
+    /// Blocks behind the best endpoint of the chain at the last probe.
+    pub lag: Option<u64>,
+    pub latency_ms: Option<u64>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+    /// Unix timestamp of the last probe.
+    pub checked_at: Option<i64>,
+}
+
+impl EndpointStatus {
+    fn is_healthy(&self) -> bool {
+        self.consecutive_failures < FAILURE_THRESHOLD && self.lag.unwrap_or(0) <= *MAX_BLOCK_LAG
+    }
+}
+
+/// Health of the endpoints of a chain.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChainStatus {
+    pub chain: String,
+    pub endpoints: Vec<EndpointStatus>,
+}
+
+struct Endpoint {
+    http: Http<Client>,
+    /// Provider talking to this endpoint alone, for health probes.
+    probe: RootProvider,
+    status: RwLock<EndpointStatus>,
+}
+
+impl Endpoint {
+    fn new(endpoint: &RpcEndpoint) -> Result<Self, anyhow::Error> {
+        let url: Url = endpoint
+            .url
+            .parse()
+            .map_err(|e| anyhow!("invalid rpc url: {}", e))?;
+        let http = Http::new(url.clone());
+        Ok(Self {
+            probe: RootProvider::new(RpcClient::new(http.clone(), false)),
+            http,
+            status: RwLock::new(EndpointStatus {
+                url: url.origin().ascii_serialization(),
+                weight: endpoint.weight,
+                healthy: true,
+                block_number: None,
+                lag: None,
+                latency_ms: None,
+                consecutive_failures: 0,
+                last_error: None,
+                checked_at: None,
+            }),
+        })
+    }
+
+    // Apply `f` to the status and log when the endpoint goes in or out of rotation.
+    fn update(&self, chain: &str, f: impl FnOnce(&mut EndpointStatus)) {
+        let mut status = self.status.write().unwrap();
+        f(&mut status);
+        let healthy = status.is_healthy();
+        if healthy != status.healthy {
+            if healthy {
+                log::info!("rpc {} of {} is healthy again", status.url, chain);
+            } else {
+                log::warn!(
+                    "rpc {} of {} is unhealthy (lag {:?}, error {:?})",
+                    status.url,
+                    chain,
+                    status.lag,
+                    status.last_error
+                );
+            }
+        }
+        status.healthy = healthy;
+    }
+
+    fn url(&self) -> String {
+        self.status.read().unwrap().url.clone()
+    }
+}
+
+/// Order in which to try endpoints given their `(weight, healthy)`.
+///
+/// Healthy endpoints come first, starting at the one `ticket` falls on when tickets are
+/// spread by weight, so that consecutive calls share the load. Endpoints of weight 0 are
+/// backups and follow them; unhealthy endpoints are the last resort.
+fn call_order(endpoints: &[(u32, bool)], ticket: usize) -> Vec<usize> {
+    let active: Vec<usize> = (0..endpoints.len())
+        .filter(|&i| endpoints[i].1 && endpoints[i].0 > 0)
+        .collect();
+    let total: usize = active.iter().map(|&i| endpoints[i].0 as usize).sum();
+
+    let mut order = Vec::with_capacity(endpoints.len());
+    if total > 0 {
+        let mut slot = ticket % total;
+        let first = active
+            .iter()
+            .position(|&i| {
+                let weight = endpoints[i].0 as usize;
+                if slot < weight {
+                    true
+                } else {
+                    slot -= weight;
+                    false
+                }
+            })
+            .unwrap_or(0);
+        order.extend(active[first..].iter().chain(&active[..first]));
+    }
+    order.extend((0..endpoints.len()).filter(|&i| endpoints[i].1 && endpoints[i].0 == 0));
+    order.extend((0..endpoints.len()).filter(|&i| !endpoints[i].1));
+    order
+}
+
+/// Transport spreading the calls of a chain over its endpoints.
+///
+/// A call that fails at the transport level (connection, timeout, HTTP status) moves on
+/// to the next endpoint; once all of them failed it is retried with exponential backoff,
+/// up to `MAX_RETRIES` times. JSON-RPC errors such as reverts are answers and are
+/// returned as is.
+///
+/// Sends are never retried: a send that timed out may still have reached its node, and
+/// broadcasting it again elsewhere could get it rejected or, once its nonce is used,
+/// hide that it went through. They go to a single endpoint, and the caller reconciles.
+#[derive(Clone)]
+pub struct FailoverTransport {
+    chain: String,
+    endpoints: Arc<Vec<Endpoint>>,
+    next: Arc<AtomicUsize>,
+}
+
+impl FailoverTransport {
+    /// Endpoints to try for the next call, only the pinned one within `pinned`.
+    fn order(&self) -> Vec<usize> {
+        let health: Vec<(u32, bool)> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let status = endpoint.status.read().unwrap();
+                (status.weight, status.healthy)
+            })
+            .collect();
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        let order = call_order(&health, ticket);
+        let Some(&first) = order.first() else {
+            return order;
+        };
+        match PINNED.try_with(|pins| {
+            *pins
+                .lock()
+                .unwrap()
+                .entry(self.chain.clone())
+                .or_insert(first)
+        }) {
+            Ok(pinned) => vec![pinned],
+            Err(_) => order,
+        }
+    }
+
+    async fn send(self, request: RequestPacket) -> Result<ResponsePacket, TransportError> {
+        let (retries, endpoints) = if is_send(&request) {
+            (0, 1)
+        } else {
+            (*MAX_RETRIES, usize::MAX)
+        };
+        let mut last_error = None;
+        for attempt in 0..=retries {
+            if attempt > 0 {
+                tokio::time::sleep(*RETRY_BACKOFF * 2u32.pow(attempt as u32 - 1)).await;
+            }
+            for i in self.order().into_iter().take(endpoints) {
+                let endpoint = &self.endpoints[i];
+                match endpoint.http.clone().call(request.clone()).await {
+                    Ok(response) => {
+                        endpoint.update(&self.chain, |status| status.consecutive_failures = 0);
+                        return Ok(response);
+                    }
+                    Err(e) => {
+                        log::warn!("rpc {} of {} failed: {}", endpoint.url(), self.chain, e);
+                        endpoint.update(&self.chain, |status| {
+                            status.consecutive_failures += 1;
+                            status.last_error = Some(e.to_string());
+                        });
+                        last_error = Some(e);
+                    }
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            TransportErrorKind::custom_str(&format!("no rpc endpoint for {}", self.chain))
+        }))
+    }
+}
+
+impl Service<RequestPacket> for FailoverTransport {
+    type Response = ResponsePacket;
+    type Error = TransportError;
+    type Future = TransportFut<'static>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: RequestPacket) -> Self::Future {
+        Box::pin(self.clone().send(request))
+    }
+}
+
+/// The RPC client of a chain and the health of its endpoints.
+#[derive(Clone)]
+pub struct ChainRpc {
+    pub provider: RootProvider,
+    transport: FailoverTransport,
+}
+
+impl ChainRpc {
+    pub fn connect(chain_info: &ChainInfo) -> Result<Self, anyhow::Error> {
+        Self::new(&chain_info.chain, &chain_info.endpoints())
+    }
+
+    fn new(chain: &str, endpoints: &[RpcEndpoint]) -> Result<Self, anyhow::Error> {
+        let endpoints = endpoints
+            .iter()
+            .map(Endpoint::new)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("{}: {}", chain, e))?;
+        let transport = FailoverTransport {
+            chain: chain.to_string(),
+            endpoints: Arc::new(endpoints),
+            next: Arc::new(AtomicUsize::new(0)),
+        };
+        Ok(Self {
+            provider: RootProvider::new(RpcClient::new(transport.clone(), false)),
+            transport,
+        })
+    }
+
+    /// Read the block number of every endpoint and update their health.
+    pub async fn probe(&self) {
+        let endpoints = self.transport.endpoints.iter();
+        let results = join_all(endpoints.map(|endpoint| async move {
+            let start = Instant::now();
+            let result = tokio::time::timeout(*MAX_LATENCY, endpoint.probe.get_block_number())
+                .await
+                .map_err(|_| format!("no answer within {} ms", MAX_LATENCY.as_millis()))
+                .and_then(|result| result.map_err(|e| e.to_string()));
+            (result, start.elapsed())
+        }))
+        .await;
+
+        let head = results
+            .iter()
+            .filter_map(|(result, _)| result.as_ref().ok())
+            .max()
+            .copied();
+        let checked_at = chrono::Utc::now().timestamp();
+        for (endpoint, (result, latency)) in self.transport.endpoints.iter().zip(results) {
+            endpoint.update(&self.transport.chain, |status| {
+                status.checked_at = Some(checked_at);
+                match result {
+                    Ok(block_number) => {
+                        status.block_number = Some(block_number);
+                        status.lag = head.map(|head| head.saturating_sub(block_number));
+                        status.latency_ms = Some(latency.as_millis() as u64);
+                        status.consecutive_failures = 0;
+                        status.last_error = None;
+                    }
+                    Err(e) => {
+                        // Out of rotation until a probe or a call gets through again.
+                        status.lag = None;
+                        status.latency_ms = None;
+                        status.consecutive_failures =
+                            status.consecutive_failures.max(FAILURE_THRESHOLD);
+                        status.last_error = Some(e);
+                    }
+                }
+            });
+        }
+    }
+
+    pub fn status(&self) -> ChainStatus {
+        ChainStatus {
+            chain: self.transport.chain.clone(),
+            endpoints: self
+                .transport
+                .endpoints
+                .iter()
+                .map(|endpoint| endpoint.status.read().unwrap().clone())
+                .collect(),
+        }
+    }
+}
+
+/// Health of every configured chain.
+pub fn chain_statuses() -> Result<Vec<ChainStatus>, anyhow::Error> {
+    CHAIN_INFOS
+        .iter()
+        .map(|info| Ok(PROVIDER_POOL.rpc(&info.chain)?.status()))
+        .collect()
+}
+
+/// Start the background task that probes the endpoints of every configured chain.
+pub fn start_health_checks() {
+    tokio::spawn(async move {
+        loop {
+            let rpcs = CHAIN_INFOS
+                .iter()
+                .filter_map(|info| match PROVIDER_POOL.rpc(&info.chain) {
+                    Ok(rpc) => Some(rpc),
+                    Err(e) => {
+                        log::error!("rpc health check error: {}", e);
+                        None
+                    }
+                });
+            join_all(rpcs.map(|rpc| async move { rpc.probe().await })).await;
+            tokio::time::sleep(*PROBE_INTERVAL).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{routing::post, Json, Router};
+    use serde_json::{json, Value};
+
+    #[test]
+    fn test_call_order() {
+        let endpoints = [(2, true), (1, true), (0, true), (1, false)];
+        assert_eq!(call_order(&endpoints, 0), vec![0, 1, 2, 3]);
+        assert_eq!(call_order(&endpoints, 1), vec![0, 1, 2, 3]);
+        assert_eq!(call_order(&endpoints, 2), vec![1, 0, 2, 3]);
+        assert_eq!(call_order(&endpoints, 3), vec![0, 1, 2, 3]);
+
+        // With every weighted endpoint down, backups go first.
+        assert_eq!(call_order(&[(1, false), (0, true)], 7), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let info: ChainInfo = serde_json::from_value(json!({
+            "chain": "test",
+            "provider_url": "http://localhost:8545",
+            "tokens": {},
+            "swap_router": "0x0000000000000000000000000000000000000000"
+        }))
+        .unwrap();
+        let endpoints = info.endpoints();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].url, "http://localhost:8545");
+        assert_eq!(endpoints[0].weight, 1);
+
+        let info: ChainInfo = serde_json::from_value(json!({
+            "chain": "test",
+            "rpcs": [
+                {"url": "https://rpc.example/v2/key", "weight": 3},
+                {"url": "https://backup.example"}
+            ],
+            "tokens": {},
+            "swap_router": "0x0000000000000000000000000000000000000000"
+        }))
+        .unwrap();
+        let rpc = ChainRpc::connect(&info).unwrap();
+        let status = rpc.status();
+        assert_eq!(status.endpoints[0].url, "https://rpc.example");
+        assert_eq!(status.endpoints[0].weight, 3);
+        assert_eq!(status.endpoints[1].weight, 1);
+    }
+
+    // JSON-RPC stub answering every call with block 0x10.
+    async fn stub_node() -> String {
+        let app = Router::new().route(
+            "/",
+            post(|Json(request): Json<Value>| async move {
+                Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": "0x10"}))
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        // Nothing listens on the first endpoint once its listener is dropped.
+        let dead = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_url = format!("http://{}", dead.local_addr().unwrap());
+        drop(dead);
+        let endpoints = [
+            RpcEndpoint {
+                url: dead_url,
+                weight: 1,
+            },
+            RpcEndpoint {
+                url: stub_node().await,
+                weight: 1,
+            },
+        ];
+
+        let rpc = ChainRpc::new("test", &endpoints).unwrap();
+        assert_eq!(rpc.provider.get_block_number().await.unwrap(), 16);
+        let status = rpc.status();
+        assert_eq!(status.endpoints[0].consecutive_failures, 1);
+        assert_eq!(status.endpoints[1].consecutive_failures, 0);
+
+        rpc.probe().await;
+        let status = rpc.status();
+        assert!(!status.endpoints[0].healthy);
+        assert!(status.endpoints[1].healthy);
+        assert_eq!(status.endpoints[1].block_number, Some(16));
+        assert_eq!(status.endpoints[1].lag, Some(0));
+
+        // Sends go to a single endpoint, here the dead one.
+        let rpc = ChainRpc::new("test", &endpoints).unwrap();
+        let sent = rpc
+            .provider
+            .client()
+            .request::<_, String>("eth_sendRawTransaction", ("0x00",))
+            .await;
+        assert!(sent.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pinned() {
+        let dead = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_url = format!("http://{}", dead.local_addr().unwrap());
+        drop(dead);
+        let endpoints = [
+            RpcEndpoint {
+                url: stub_node().await,
+                weight: 1,
+            },
+            RpcEndpoint {
+                url: dead_url,
+                weight: 1,
+            },
+        ];
+
+        // The second call would start at the dead endpoint if it wasn't pinned.
+        let rpc = ChainRpc::new("test", &endpoints).unwrap();
+        pinned(async {
+            assert_eq!(rpc.provider.get_block_number().await.unwrap(), 16);
+            assert_eq!(rpc.provider.get_block_number().await.unwrap(), 16);
+        })
+        .await;
+        assert_eq!(rpc.status().endpoints[1].consecutive_failures, 0);
+    }
+}
@@ -1,207 +1,367 @@
 use crate::chains::get_chain_info;
+use crate::contracts::amount::TokenAmount;
+use crate::policy::{self, token_name, Action};
+use crate::wallets::{
+    accounts, execute_after, execution::ExecutionMode, BalanceChange, Execution, TxContext,
+    EXECUTION_MODE,
+};
 use alloy::{
-    network::EthereumWallet,
-    primitives::{utils::parse_ether, Address, TxHash, B256, U256},
-    providers::{ProviderBuilder, WalletProvider},
-    signers::local::PrivateKeySigner,
+    primitives::{Address, U256},
+    providers::RootProvider,
+    rpc::types::TransactionRequest,
     sol,
 };
 use anyhow::{anyhow, Result};
-use rig_derive::rig_tool;
 use serde::{Deserialize, Serialize};
-use std::{str::FromStr, sync::Arc};
-
-use super::common::make_provider;
+use std::str::FromStr;
 
-const MAX_AMOUNT: u128 = 10u128;
+use super::abi::{IUniswapV2Factory, IUniswapV2Pair};
+use super::common::{
+    make_provider, min_amount_out, price_impact_bps, rpc_error, QuoteFailure, SwapParams,
+    MAX_PRICE_IMPACT_BPS,
+};
+use super::erc20::{approve_erc20, approve_erc20_request, check_allowance_erc20};
+use super::weth::weth_address;
 
 sol! {
     #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
     #[sol(rpc)]
     interface IROUTER {
         function swapExactETHForTokens(uint amountOutMin, address[] calldata path, address to, uint deadline) payable returns (uint[] memory amounts);
+        function swapExactTokensForETH(uint amountIn, uint amountOutMin, address[] calldata path, address to, uint deadline) returns (uint[] memory amounts);
+        function swapExactTokensForTokens(uint amountIn, uint amountOutMin, address[] calldata path, address to, uint deadline) returns (uint[] memory amounts);
+        function swapExactETHForTokensSupportingFeeOnTransferTokens(uint amountOutMin, address[] calldata path, address to, uint deadline) payable;
+        function swapExactTokensForETHSupportingFeeOnTransferTokens(uint amountIn, uint amountOutMin, address[] calldata path, address to, uint deadline);
+        function swapExactTokensForTokensSupportingFeeOnTransferTokens(uint amountIn, uint amountOutMin, address[] calldata path, address to, uint deadline);
         function getAmountsOut(uint amountIn, address[] memory path)public view returns (uint[] memory amounts);
+        function factory() external pure returns (address);
     }
 }
 
-#[rig_tool(
-    description = "Swap ETH for a specific ERC20 token",
-    params(
-        chain = "The chain name, such as arbitrum",
-        token_address = "The address of the ERC20 token to receive",
-        amount = "The amount of ETH to swap"
-    )
-)]
-pub async fn eth_swap_to_erc20(
-    chain: String,
-    token_address: String,
-    amount: String,
-) -> Result<String, rig::tool::ToolError> {
-    let token_address = Address::from_str(&token_address)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid token_address format".into()))?;
-
-    let amount = u128::from_str(&amount)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid amount format".into()))?;
-
-    println!(
-        "chain_name: {}, token_address: {}, amount: {}",
-        chain, token_address, amount
-    );
-
-    if amount > MAX_AMOUNT {
-        println!(
-            "amount = {} exceeds the safe value = {}",
-            amount, MAX_AMOUNT
-        );
-        return Err(rig::tool::ToolError::ToolCallError(
-            format!(
-                "amount = {} exceeds the safe value = {}",
-                amount, MAX_AMOUNT
-            )
-            .into(),
+/// Router and WETH addresses configured for a chain.
+fn router_info(chain_name: &str) -> Result<(Address, Address)> {
+    let chain_info =
+        get_chain_info(chain_name).ok_or_else(|| anyhow!("unknown chain: {}", chain_name))?;
+    let router = Address::from_str(&chain_info.swap_router)
+        .map_err(|_| anyhow!("Invalid swap_router address format"))?;
+    Ok((router, weth_address(chain_name)?))
+}
+
+/// Router path for a swap; `Address::ZERO` stands for ETH and trades as WETH.
+///
+/// Token to token swaps hop through WETH unless one side already is WETH.
+pub fn v2_path(weth: Address, input_token: Address, output_token: Address) -> Vec<Address> {
+    let input_token = if input_token == Address::ZERO {
+        weth
+    } else {
+        input_token
+    };
+    let output_token = if output_token == Address::ZERO {
+        weth
+    } else {
+        output_token
+    };
+    if input_token == weth || output_token == weth {
+        vec![input_token, output_token]
+    } else {
+        vec![input_token, weth, output_token]
+    }
+}
+
+/// `(reserve_in, reserve_out)` of every pair along `path`.
+async fn path_reserves(
+    provider: &RootProvider,
+    factory: Address,
+    path: &[Address],
+) -> Result<Vec<(U256, U256)>> {
+    let factory = IUniswapV2Factory::IUniswapV2FactoryInstance::new(factory, provider.clone());
+    let mut reserves = Vec::with_capacity(path.len().saturating_sub(1));
+    for hop in path.windows(2) {
+        let pair = factory
+            .getPair(hop[0], hop[1])
+            .call()
+            .await
+            .map_err(|e| anyhow!("getPair error: {}", e))?
+            .pair;
+        if pair == Address::ZERO {
+            return Err(anyhow!("no pair for {} and {}", hop[0], hop[1]));
+        }
+        let pair = IUniswapV2Pair::IUniswapV2PairInstance::new(pair, provider.clone());
+        let token0 = pair
+            .token0()
+            .call()
+            .await
+            .map_err(|e| anyhow!("token0 error: {}", e))?
+            ._0;
+        let reserve = pair
+            .getReserves()
+            .call()
+            .await
+            .map_err(|e| anyhow!("getReserves error: {}", e))?;
+        let (reserve0, reserve1) = (U256::from(reserve.reserve0), U256::from(reserve.reserve1));
+        reserves.push(if token0 == hop[0] {
+            (reserve0, reserve1)
+        } else {
+            (reserve1, reserve0)
+        });
+    }
+    Ok(reserves)
+}
+
+/// Quote `amount` along `path` and refuse the trade if its price impact is too high.
+async fn quote_v2(
+    provider: &RootProvider,
+    router: Address,
+    amount: U256,
+    path: &[Address],
+) -> std::result::Result<U256, QuoteFailure> {
+    let router = IROUTER::IROUTERInstance::new(router, provider.clone());
+    let failure = |reason: String| QuoteFailure::new("uniswap_v2", path.to_vec(), reason);
+
+    let expected_amount = router
+        .getAmountsOut(amount, path.to_vec())
+        .call()
+        .await
+        .map_err(|e| failure(format!("getAmountsOut error: {}", e)))?
+        .amounts
+        .last()
+        .copied()
+        .ok_or_else(|| failure("getAmountsOut returned no amounts".to_string()))?;
+
+    let factory = router
+        .factory()
+        .call()
+        .await
+        .map_err(|e| failure(format!("factory error: {}", e)))?
+        ._0;
+    let reserves = path_reserves(provider, factory, path)
+        .await
+        .map_err(|e| failure(e.to_string()))?;
+    let impact = price_impact_bps(amount, expected_amount, &reserves);
+    if impact > *MAX_PRICE_IMPACT_BPS {
+        let mut failure = failure(format!(
+            "price impact of {} bps is above the allowed maximum of {} bps",
+            impact, *MAX_PRICE_IMPACT_BPS
         ));
+        failure.price_impact_bps = Some(impact);
+        return Err(failure);
     }
+    Ok(expected_amount)
+}
 
-    let chain_info = get_chain_info(&chain).ok_or(rig::tool::ToolError::ToolCallError(
-        "get_chain_info none".into(),
-    ))?;
+/// How the router gets to pull the input token of a swap.
+enum Allowance {
+    /// The allowance is already there, or was just approved.
+    Ready,
+    /// The approve a dry run has to simulate together with the swap.
+    Unsent(TransactionRequest),
+    /// The approve waits for a human or the Safe's owners; the swap can't be built yet.
+    Pending(Execution),
+}
 
-    let weth = chain_info
-        .tokens
-        .iter()
-        .find(|t| t.0 == "WETH")
-        .ok_or(rig::tool::ToolError::ToolCallError(
-            "WETH token not found".into(),
-        ))?
-        .1;
-
-    let path: Vec<Address> = vec![
-        Address::from_str(weth).map_err(|_| {
-            rig::tool::ToolError::ToolCallError("Invalid WETH address format".into())
-        })?,
-        token_address,
-    ]; // ETH -> Token
-
-    let result = swap_eth_to_erc20(
-        Address::from_str(&chain_info.swap_router).map_err(|_| {
-            rig::tool::ToolError::ToolCallError("Invalid swap_router address format".into())
-        })?,
-        parse_ether(&amount.to_string()).unwrap_or_default(),
-        path,
-        chain,
-    )
-    .await;
+/// Make sure the router may pull `amount` of `token` from `account`, approving it if
+/// needed. The approve is checked by the policy as an action of `tool`.
+async fn ensure_allowance(
+    tool: &str,
+    token: Address,
+    router: Address,
+    amount: U256,
+    chain_name: &str,
+    account: Option<String>,
+) -> Result<Allowance> {
+    let provider = make_provider(chain_name.to_string())?;
+    let owner = accounts::account(account.as_deref())?
+        .sender(&provider)
+        .await?;
+    let allowance = check_allowance_erc20(owner, router, token, chain_name.to_string()).await?;
+    if allowance >= amount {
+        return Ok(Allowance::Ready);
+    }
 
-    match result {
-        Ok(h) => Ok(h.to_string()),
-        Err(e) => Err(rig::tool::ToolError::ToolCallError(
-            format!("swap_eth_to_erc20 error: {}", e).into(),
-        )),
+    let approval = TokenAmount::from_raw(token, amount, chain_name).await?;
+    policy::authorize(&Action::approve(
+        tool,
+        chain_name,
+        &token_name(chain_name, token),
+        approval.as_f64(),
+        router,
+    ))?;
+    if *EXECUTION_MODE == ExecutionMode::DryRun {
+        return Ok(Allowance::Unsent(approve_erc20_request(
+            token, router, amount,
+        )));
+    }
+
+    match approve_erc20(router, amount, token, chain_name.to_string(), account).await? {
+        execution @ (Execution::PendingApproval(_) | Execution::Proposed(_)) => {
+            Ok(Allowance::Pending(execution))
+        }
+        execution => {
+            log::info!(
+                "approved {} of {} for router {}: {}",
+                approval,
+                token,
+                router,
+                execution
+            );
+            Ok(Allowance::Ready)
+        }
     }
 }
 
-async fn swap_eth_to_erc20(
-    router_address: Address,
+/// Build the router transaction swapping an exact `amount` of `input_token` for
+/// `output_token`, paid to `recipient`.
+///
+/// `Address::ZERO` on either side means native ETH. With `fee_on_transfer` the
+/// `SupportingFeeOnTransferTokens` variants are used; their quote ignores the token's
+/// fee, so the slippage in `params` has to cover it. Returns the router address, the
+/// request and the quoted output.
+pub async fn swap_v2_request(
+    provider: &RootProvider,
+    chain_name: &str,
+    input_token: Address,
+    output_token: Address,
+    amount: U256,
+    fee_on_transfer: bool,
+    params: SwapParams,
+    recipient: Address,
+) -> Result<(Address, TransactionRequest, U256)> {
+    let (router_address, weth) = router_info(chain_name)?;
+    let path = v2_path(weth, input_token, output_token);
+
+    let expected_amount = quote_v2(provider, router_address, amount, &path).await?;
+    let amount_out_min = min_amount_out(expected_amount, params.slippage_bps);
+
+    let router = IROUTER::IROUTERInstance::new(router_address, provider.clone());
+    let to = recipient;
+    let deadline = params.deadline();
+    let request: TransactionRequest = match (
+        input_token == Address::ZERO,
+        output_token == Address::ZERO,
+        fee_on_transfer,
+    ) {
+        (true, _, false) => router
+            .swapExactETHForTokens(amount_out_min, path, to, deadline)
+            .value(amount)
+            .into_transaction_request(),
+        (true, _, true) => router
+            .swapExactETHForTokensSupportingFeeOnTransferTokens(amount_out_min, path, to, deadline)
+            .value(amount)
+            .into_transaction_request(),
+        (false, true, false) => router
+            .swapExactTokensForETH(amount, amount_out_min, path, to, deadline)
+            .into_transaction_request(),
+        (false, true, true) => router
+            .swapExactTokensForETHSupportingFeeOnTransferTokens(
+                amount,
+                amount_out_min,
+                path,
+                to,
+                deadline,
+            )
+            .into_transaction_request(),
+        (false, false, false) => router
+            .swapExactTokensForTokens(amount, amount_out_min, path, to, deadline)
+            .into_transaction_request(),
+        (false, false, true) => router
+            .swapExactTokensForTokensSupportingFeeOnTransferTokens(
+                amount,
+                amount_out_min,
+                path,
+                to,
+                deadline,
+            )
+            .into_transaction_request(),
+    };
+    Ok((router_address, request, expected_amount))
+}
+
+/// Swap an exact input through the V2-style router of `chain_name`, approving the
+/// router first if needed. The output goes to `account`; see `swap_v2_request` for
+/// the other arguments.
+pub async fn swap_v2(
+    tool: &str,
+    input_token: Address,
+    output_token: Address,
     amount: U256,
-    path: Vec<Address>,
+    fee_on_transfer: bool,
+    params: SwapParams,
     chain_name: String,
-) -> std::result::Result<B256, anyhow::Error> {
-    let private_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
-    let signer: PrivateKeySigner = private_key.parse().expect("parse PrivateKeySigner");
-    let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
-
-    let provider = make_provider(chain_name)?;
-
-    let eth_signer = Arc::new(
-        ProviderBuilder::new()
-            .wallet(wallet)
-            .on_provider(provider.clone()),
-    );
-
-    // Create contract instance.
-    let swap_router_instance = IROUTER::IROUTERInstance::new(router_address, eth_signer.clone());
-
-    // Prepare swap func params.
-    let receive_address = eth_signer.default_signer_address();
-    let deadline = U256::from(
-        std::time::SystemTime::now()
-            .duration_since(std::time::SystemTime::UNIX_EPOCH)
-            .expect("Time error")
-            .as_secs()
-            + 1200,
-    ); // 20 minutes deadline
-
-    let tx_hash: std::result::Result<TxHash, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            
-            tokio::runtime::Handle::current().block_on(async {
-                let expected_amount: U256 = swap_router_instance
-                    .getAmountsOut(amount, path.clone())
-                    .call()
-                    .await
-                    .unwrap()
-                    .amounts[1];
-                //Calculate amount_out_min (for example, set a slippage of 0.5%)
-                let slippage = U256::from(5); // 0.5%
-                let amount_out_min =
-                    expected_amount * (U256::from(1000) - slippage) / U256::from(1000);
-
-                swap_router_instance
-                    .swapExactETHForTokens(amount_out_min, path, receive_address, deadline)
-                    .send()
-                    .await
-            })
-        });
-        match handle.await {
-            Ok(Ok(tx)) => Ok(*tx.tx_hash()),
-            Ok(Err(e)) => Err(anyhow!(format!("alloy rpc error: {}", e))),
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
+    account: Option<String>,
+) -> std::result::Result<Execution, anyhow::Error> {
+    let mut prior = Vec::new();
+    if input_token != Address::ZERO {
+        let (router_address, _) = router_info(&chain_name)?;
+        match ensure_allowance(
+            tool,
+            input_token,
+            router_address,
+            amount,
+            &chain_name,
+            account.clone(),
+        )
+        .await?
+        {
+            Allowance::Ready => {}
+            Allowance::Unsent(approve) => prior.push(approve),
+            Allowance::Pending(execution) => return Ok(execution),
         }
     }
-    .await;
-    tx_hash
+
+    let provider = make_provider(chain_name.clone())?;
+    let recipient = accounts::account(account.as_deref())?
+        .sender(&provider)
+        .await?;
+    let ctx = TxContext::new(&chain_name, tool).with_account(account);
+
+    let (_, request, expected_amount) = swap_v2_request(
+        &provider,
+        &chain_name,
+        input_token,
+        output_token,
+        amount,
+        fee_on_transfer,
+        params,
+        recipient,
+    )
+    .await?;
+    let changes = vec![
+        BalanceChange::outgoing(input_token, amount),
+        BalanceChange::incoming(output_token, expected_amount),
+    ];
+    execute_after(prior, request, provider, ctx, changes)
+        .await
+        .map_err(rpc_error)
+}
+
+#[test]
+fn test_v2_path() {
+    let weth = Address::repeat_byte(0xee);
+    let usdc = Address::repeat_byte(0x01);
+    let link = Address::repeat_byte(0x02);
+
+    assert_eq!(v2_path(weth, Address::ZERO, usdc), vec![weth, usdc]);
+    assert_eq!(v2_path(weth, usdc, Address::ZERO), vec![usdc, weth]);
+    assert_eq!(v2_path(weth, link, usdc), vec![link, weth, usdc]);
+    assert_eq!(v2_path(weth, weth, usdc), vec![weth, usdc]);
 }
 
 #[tokio::test]
 async fn test_swap_eth_to_erc20() -> Result<()> {
-    let weth = Address::from_str("0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2").unwrap();
     let expect_token = Address::from_str("5FbDB2315678afecb367f032d93F642f64180aa3").unwrap();
-    let path: Vec<Address> = vec![weth, expect_token]; // ETH -> Token
 
     let amount = "0.1".to_string(); // 0.1 ETH
-    let tx_hash = swap_eth_to_erc20(
-        Address::from_str("0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D").unwrap(),
-        parse_ether(&amount).unwrap(),
-        path,
-        String::from("http://localhost:8545"),
+    let tx_hash = swap_v2(
+        "eth_swap_to_erc20",
+        Address::ZERO,
+        expect_token,
+        alloy::primitives::utils::parse_ether(&amount).unwrap(),
+        false,
+        SwapParams::new(None, None)?,
+        String::from("local"),
+        None,
     )
     .await;
     println!("tx_hash:{}", tx_hash.unwrap());
     Ok(())
 }
-
-#[tokio::test]
-async fn test_run() -> Result<()> {
-    use crate::chains::CHAIN_INFOS;
-    use rig::completion::Prompt;
-    use rig::providers::openai;
-
-    let openai_client = openai::Client::from_url("sk-xxxxx", "https://api.xxxxx.xx/");
-
-    // Define the agent with the swap tool.
-    let swap_agent = openai_client
-        .agent("Qwen/Qwen2.5-32B-Instruct")
-        .preamble("You are a swap agent here to help the user perform ETH to ERC20 token swaps.")
-        .context(&serde_json::to_string(&*CHAIN_INFOS).unwrap())
-        .max_tokens(2048)
-        .tool(EthSwapToErc20)
-        .build();
-
-    // Prompt the agent and print the response.
-    println!("Swap ETH to ERC20 token");
-    println!(
-        "Swap Agent: {}",
-        swap_agent.prompt("Swap 0.1 ETH to USDC on base").await?
-    );
-    Ok(())
-}
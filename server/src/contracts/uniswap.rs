@@ -1,85 +1,619 @@
-use crate::wallets::send_tx;
-use alloy::primitives as alloy_primitives;
-use alloy::providers::Provider;
+use crate::chains::get_chain_info;
+use crate::policy::{self, token_name, Action};
+use crate::wallets::{
+    accounts, execute,
+    execution::ExecutionMode,
+    permit::{sign_erc2612_permit, vrs, Permit},
+    Account, BalanceChange, Execution, TxContext, EXECUTION_MODE,
+};
 use alloy::{
-    primitives::{Address, TxHash, B256},
-    rpc::types::TransactionRequest,
+    primitives::{
+        aliases::{U160, U24},
+        Address, Bytes, TxHash, U256,
+    },
+    providers::RootProvider,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    sol_types::{SolCall, SolEvent},
 };
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-use uniswap_sdk_core::{prelude::*, token};
-use uniswap_v3_sdk::prelude::{sdk_core::prelude::CurrencyAmount, *};
+use super::{
+    abi::{IQuoterV2, ISwapRouter02, IERC20, IWETH9},
+    amount::TokenAmount,
+    common::{make_provider, min_amount_out, rpc_error, QuoteFailure, SwapParams},
+    erc20::{approve_erc20_request, check_allowance_erc20, permit_accepted, permit_support},
+    weth::weth_address,
+};
 
-use super::common::make_provider;
+/// SwapRouter02 placeholder recipient for "keep the output in the router", used before
+/// `unwrapWETH9`.
+const ADDRESS_THIS: Address = Address::with_last_byte(2);
 
-pub async fn swap(
+/// Fee tiers of the V3 factory, in hundredths of a basis point.
+pub const FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+/// A V3 route: `tokens[i] -> tokens[i + 1]` through the pool with fee `fees[i]`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SwapRoute {
+    pub tokens: Vec<Address>,
+    pub fees: Vec<u32>,
+}
+
+impl SwapRoute {
+    pub fn single(input_token: Address, output_token: Address, fee: u32) -> Self {
+        Self {
+            tokens: vec![input_token, output_token],
+            fees: vec![fee],
+        }
+    }
+
+    pub fn input_token(&self) -> Address {
+        self.tokens[0]
+    }
+
+    pub fn output_token(&self) -> Address {
+        *self.tokens.last().expect("route has tokens")
+    }
+
+    /// Packed `token (20) | fee (3) | token (20) | ...` path used by `exactInput`.
+    pub fn encode_path(&self) -> Bytes {
+        let mut path = Vec::with_capacity(20 + self.fees.len() * 23);
+        for (i, token) in self.tokens.iter().enumerate() {
+            path.extend_from_slice(token.as_slice());
+            if let Some(fee) = self.fees.get(i) {
+                path.extend_from_slice(&fee.to_be_bytes()[1..]);
+            }
+        }
+        path.into()
+    }
+}
+
+/// Ends of a swap that are paid or received as native ETH instead of WETH.
+///
+/// The route itself always trades WETH; the router wraps the input from `msg.value` and
+/// unwraps the output with `unwrapWETH9` inside the same multicall.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NativeEth {
+    pub input: bool,
+    pub output: bool,
+}
+
+impl NativeEth {
+    /// `Address::ZERO` on either side stands for native ETH.
+    pub fn of(input_token: Address, output_token: Address) -> Self {
+        Self {
+            input: input_token == Address::ZERO,
+            output: output_token == Address::ZERO,
+        }
+    }
+}
+
+/// Replace `Address::ZERO` (native ETH) with `weth`, as pools only hold WETH.
+fn routable(token: Address, weth: Address) -> Address {
+    if token == Address::ZERO {
+        weth
+    } else {
+        token
+    }
+}
+
+/// A route together with its quoted output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RouteQuote {
+    pub route: SwapRoute,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+/// Amounts actually moved by a swap, read from the receipt's Transfer logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutedSwap {
+    pub tx_hash: TxHash,
+    pub input_token: Address,
+    pub output_token: Address,
+    pub amount_in: U256,
+    pub amount_out: U256,
+}
+
+fn v3_addresses(chain_name: &str) -> Result<(Address, Address)> {
+    let info = get_chain_info(chain_name)
+        .and_then(|info| info.uniswap_v3)
+        .ok_or_else(|| anyhow!("uniswap v3 is not configured on {}", chain_name))?;
+    Ok((
+        Address::from_str(&info.swap_router02)?,
+        Address::from_str(&info.quoter_v2)?,
+    ))
+}
+
+/// Quote `amount_in` along `route` through QuoterV2 with `eth_call`.
+pub async fn quote(
+    provider: &RootProvider,
+    quoter: Address,
+    route: &SwapRoute,
+    amount_in: U256,
+) -> Result<U256> {
+    let quoter = IQuoterV2::IQuoterV2Instance::new(quoter, provider.clone());
+    let amount_out = if route.fees.len() == 1 {
+        let params = IQuoterV2::QuoteExactInputSingleParams {
+            tokenIn: route.input_token(),
+            tokenOut: route.output_token(),
+            amountIn: amount_in,
+            fee: U24::from(route.fees[0]),
+            sqrtPriceLimitX96: U160::ZERO,
+        };
+        quoter
+            .quoteExactInputSingle(params)
+            .call()
+            .await
+            .map_err(|e| anyhow!("quoteExactInputSingle error: {}", e))?
+            .amountOut
+    } else {
+        quoter
+            .quoteExactInput(route.encode_path(), amount_in)
+            .call()
+            .await
+            .map_err(|e| anyhow!("quoteExactInput error: {}", e))?
+            .amountOut
+    };
+    Ok(amount_out)
+}
+
+/// Intermediate tokens for two-hop routes: every token listed for the chain.
+fn routing_bases(chain_name: &str) -> Vec<Address> {
+    get_chain_info(chain_name)
+        .map(|info| {
+            info.tokens
+                .values()
+                .filter_map(|address| Address::from_str(address).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Direct routes over every fee tier, plus two-hop routes through each base.
+pub fn candidate_routes(
     input_token: Address,
     output_token: Address,
-    input_amount: u128,
-    chain_name: String,
-) -> std::result::Result<B256, anyhow::Error> {
-    let provider = make_provider(chain_name).map_err(|e| {
-        rig::tool::ToolError::ToolCallError(format!("Provider error: {}", e).into())
-    })?;
-
-    // Sync send transfer call.
-    let tx_hash: std::result::Result<TxHash, anyhow::Error> = async move {
-        let handle = tokio::task::spawn_blocking(move || {
-            tokio::runtime::Handle::current().block_on(async {
-                let tx =
-                    create_swap_request(input_token, output_token, input_amount, &provider).await?;
-                // Send the transaction and return the transaction hash
-                send_tx(tx, provider).await
-            })
-        });
-        match handle.await {
-            Ok(Ok(tx)) => Ok(tx.transaction_hash),
-            Ok(Err(e)) => Err(anyhow!(format!("alloy rpc error: {}", e))),
-            Err(e) => Err(anyhow!(format!("tokio exec error: {}", e))),
+    bases: &[Address],
+) -> Vec<SwapRoute> {
+    let mut routes: Vec<SwapRoute> = FEE_TIERS
+        .iter()
+        .map(|fee| SwapRoute::single(input_token, output_token, *fee))
+        .collect();
+
+    for base in bases {
+        if *base == input_token || *base == output_token {
+            continue;
+        }
+        for first in FEE_TIERS {
+            for second in FEE_TIERS {
+                routes.push(SwapRoute {
+                    tokens: vec![input_token, *base, output_token],
+                    fees: vec![first, second],
+                });
+            }
         }
     }
+    routes
+}
+
+/// Quote every candidate route concurrently and keep the one with the highest output.
+///
+/// Routes through pools that don't exist make the quoter revert and are skipped.
+pub async fn best_route(
+    provider: &RootProvider,
+    quoter: Address,
+    candidates: Vec<SwapRoute>,
+    amount_in: U256,
+) -> Result<RouteQuote> {
+    let quotes = futures::future::join_all(
+        candidates
+            .iter()
+            .map(|route| quote(provider, quoter, route, amount_in)),
+    )
     .await;
-    tx_hash
+
+    candidates
+        .into_iter()
+        .zip(quotes)
+        .filter_map(|(route, quoted)| match quoted {
+            Ok(amount_out) if !amount_out.is_zero() => Some(RouteQuote {
+                route,
+                amount_in,
+                amount_out,
+            }),
+            _ => None,
+        })
+        .max_by_key(|quote| quote.amount_out)
+        .ok_or_else(|| anyhow!("no uniswap v3 route with liquidity found"))
 }
 
-async fn create_swap_request(
+/// Find the best route from `input_token` to `output_token` on `chain_name`.
+///
+/// `Address::ZERO` on either side is native ETH and is routed as WETH.
+pub async fn quote_best_route(
     input_token: Address,
     output_token: Address,
-    input_amount: u128,
-    provider: &alloy::providers::RootProvider,
-) -> Result<TransactionRequest, anyhow::Error> {
-    let chain_id = provider.get_chain_id().await?;
-
-    let pool = Pool::<EphemeralTickMapDataProvider>::from_pool_key_with_tick_data_provider(
-        chain_id,
-        FACTORY_ADDRESS,
-        input_token,
-        output_token,
-        FeeAmount::MEDIUM,
-        provider.clone(),
-        None,
-    )
-    .await
-    .map_err(|e| anyhow!(format!("Pool creation error: {}", e)))?;
-    let amount_in = CurrencyAmount::from_raw_amount(
-        token!(chain_id, input_token, 18, "INPUT_TOKEN", "INPUT_TOKEN"),
-        input_amount,
-    )?;
-
-    let route = Route::new(
-        vec![pool],
-        token!(chain_id, input_token, 18, "INPUT_TOKEN", "INPUT_TOKEN"),
-        token!(chain_id, output_token, 18, "OUTPUT_TOKEN", "OUTPUT_TOKEN"),
-    );
-
-    let params = quote_call_parameters(&route, &amount_in, TradeType::ExactInput, None);
-    let tx = TransactionRequest::default()
-        .to(*QUOTER_ADDRESSES
-            .get(&chain_id)
-            .expect("chain supported by uniswap"))
-        .input(params.calldata.into());
-    Ok(tx)
-}
-
-#[test]
-fn test_swap() {}
+    amount_in: U256,
+    chain_name: String,
+) -> std::result::Result<RouteQuote, anyhow::Error> {
+    let (_, quoter) = v3_addresses(&chain_name)?;
+    let weth = weth_address(&chain_name)?;
+    let input_token = routable(input_token, weth);
+    let output_token = routable(output_token, weth);
+    let provider = make_provider(chain_name.clone())?;
+    let candidates = candidate_routes(input_token, output_token, &routing_bases(&chain_name));
+
+    best_route(&provider, quoter, candidates, amount_in)
+        .await
+        .map_err(|e| {
+            anyhow::Error::from(QuoteFailure::new(
+                "uniswap_v3",
+                vec![input_token, output_token],
+                e,
+            ))
+        })
+}
+
+/// SwapRouter02 calls of a swap: `exactInput*`, then `unwrapWETH9` for native output.
+fn swap_calls(
+    route: &SwapRoute,
+    native: NativeEth,
+    amount_in: U256,
+    amount_out_minimum: U256,
+    recipient: Address,
+) -> Vec<Bytes> {
+    // Native output is swapped into the router first and unwrapped to the recipient after.
+    let swap_recipient = if native.output {
+        ADDRESS_THIS
+    } else {
+        recipient
+    };
+    let call = if route.fees.len() == 1 {
+        ISwapRouter02::exactInputSingleCall {
+            params: ISwapRouter02::ExactInputSingleParams {
+                tokenIn: route.input_token(),
+                tokenOut: route.output_token(),
+                fee: U24::from(route.fees[0]),
+                recipient: swap_recipient,
+                amountIn: amount_in,
+                amountOutMinimum: amount_out_minimum,
+                sqrtPriceLimitX96: U160::ZERO,
+            },
+        }
+        .abi_encode()
+    } else {
+        ISwapRouter02::exactInputCall {
+            params: ISwapRouter02::ExactInputParams {
+                path: route.encode_path(),
+                recipient: swap_recipient,
+                amountIn: amount_in,
+                amountOutMinimum: amount_out_minimum,
+            },
+        }
+        .abi_encode()
+    };
+    let mut data = vec![call.into()];
+    if native.output {
+        data.push(
+            ISwapRouter02::unwrapWETH9Call {
+                amountMinimum: amount_out_minimum,
+                recipient,
+            }
+            .abi_encode()
+            .into(),
+        );
+    }
+    data
+}
+
+/// Build the SwapRouter02 `multicall(deadline, data)` transaction paying `value` in ETH.
+fn create_swap_request(
+    router: Address,
+    data: Vec<Bytes>,
+    value: U256,
+    deadline: U256,
+) -> TransactionRequest {
+    let multicall = ISwapRouter02::multicallCall { deadline, data };
+    let request = TransactionRequest::default()
+        .to(router)
+        .input(multicall.abi_encode().into());
+    if value.is_zero() {
+        request
+    } else {
+        request.value(value)
+    }
+}
+
+/// How the router gets to pull the input token of a swap.
+enum Allowance {
+    /// The allowance is already there, or was just approved.
+    Ready,
+    /// A signed `selfPermit` call to run in the swap's multicall.
+    Permit(Bytes),
+    /// The approve waits for a human or the Safe's owners; the swap can't be sent yet.
+    Pending(Execution),
+}
+
+/// Make sure the router may pull `amount` of `token` from `account`.
+///
+/// Tokens with EIP-2612 support get a signed `selfPermit` call, which saves the approval
+/// transaction. Other tokens are approved first through `execute`, so the approve is
+/// checked by the policy and may be parked or proposed like any other transaction; it
+/// is only broadcast in live mode.
+async fn ensure_allowance(
+    provider: &RootProvider,
+    chain_name: &str,
+    token: Address,
+    account: &Account,
+    router: Address,
+    amount: U256,
+    deadline: U256,
+) -> Result<Allowance> {
+    let owner = account.sender(provider).await?;
+    let allowance = check_allowance_erc20(owner, router, token, chain_name.to_string()).await?;
+    if allowance >= amount {
+        return Ok(Allowance::Ready);
+    }
+
+    if let Some(support) = permit_support(provider, token, owner).await? {
+        let permit = Permit {
+            owner,
+            spender: router,
+            value: amount,
+            nonce: support.nonce,
+            deadline,
+        };
+        let signature = sign_erc2612_permit(&account.signer, &permit, &support.domain).await?;
+        if permit_accepted(provider, token, &permit, &signature).await {
+            let (v, r, s) = vrs(&signature);
+            let call = ISwapRouter02::selfPermitCall {
+                token,
+                value: amount,
+                deadline,
+                v,
+                r,
+                s,
+            };
+            log::info!("signed permit of {} for router {}", token, router);
+            return Ok(Allowance::Permit(call.abi_encode().into()));
+        }
+        log::info!(
+            "{} rejected the permit for router {}, approving",
+            token,
+            router
+        );
+    }
+
+    let approval = TokenAmount::from_raw(token, amount, chain_name).await?;
+    policy::authorize(&Action::approve(
+        "uniswap_v3_swap",
+        chain_name,
+        &token_name(chain_name, token),
+        approval.as_f64(),
+        router,
+    ))?;
+    if *EXECUTION_MODE != ExecutionMode::Live {
+        log::warn!(
+            "allowance of {} for router {} is {}, approval skipped in {:?} mode",
+            token,
+            router,
+            allowance,
+            *EXECUTION_MODE
+        );
+        return Ok(Allowance::Ready);
+    }
+
+    let request = approve_erc20_request(token, router, amount);
+    let ctx = TxContext::new(chain_name, "uniswap_v3_swap:approve")
+        .with_account(Some(account.name.clone()));
+    match execute(request, provider.clone(), ctx, vec![]).await? {
+        execution @ (Execution::PendingApproval(_) | Execution::Proposed(_)) => {
+            Ok(Allowance::Pending(execution))
+        }
+        execution => {
+            log::info!(
+                "approved {} of {} for router {}: {}",
+                approval,
+                token,
+                router,
+                execution
+            );
+            Ok(Allowance::Ready)
+        }
+    }
+}
+
+/// Sum the Transfer logs of the route's input and output tokens for `account`.
+///
+/// Native legs move no ERC20 from or to `account`; they are read from the WETH
+/// `Deposit` and `Withdrawal` logs of the router instead.
+pub fn executed_amounts(
+    receipt: &TransactionReceipt,
+    account: Address,
+    route: &SwapRoute,
+    native: NativeEth,
+) -> ExecutedSwap {
+    let mut amount_in = U256::ZERO;
+    let mut amount_out = U256::ZERO;
+    for log in receipt.inner.logs() {
+        if let Ok(transfer) = IERC20::Transfer::decode_log(&log.inner, true) {
+            if !native.input && transfer.address == route.input_token() && transfer.from == account
+            {
+                amount_in += transfer.value;
+            }
+            if !native.output && transfer.address == route.output_token() && transfer.to == account
+            {
+                amount_out += transfer.value;
+            }
+        } else if let Ok(deposit) = IWETH9::Deposit::decode_log(&log.inner, true) {
+            if native.input && deposit.address == route.input_token() {
+                amount_in += deposit.wad;
+            }
+        } else if let Ok(withdrawal) = IWETH9::Withdrawal::decode_log(&log.inner, true) {
+            if native.output && withdrawal.address == route.output_token() {
+                amount_out += withdrawal.wad;
+            }
+        }
+    }
+    let token = |native: bool, token: Address| if native { Address::ZERO } else { token };
+    ExecutedSwap {
+        tx_hash: receipt.transaction_hash,
+        input_token: token(native.input, route.input_token()),
+        output_token: token(native.output, route.output_token()),
+        amount_in,
+        amount_out,
+    }
+}
+
+/// Swap `amount_in` along `route` for `account`, paying or receiving native ETH on the
+/// `native` ends.
+pub async fn swap(
+    route: SwapRoute,
+    native: NativeEth,
+    amount_in: U256,
+    params: SwapParams,
+    chain_name: String,
+    account: Option<String>,
+) -> std::result::Result<Execution, anyhow::Error> {
+    let (router, quoter) = v3_addresses(&chain_name)?;
+    let provider = make_provider(chain_name.clone())?;
+    let ctx = TxContext::new(&chain_name, "uniswap_v3_swap").with_account(account.clone());
+    let account = accounts::account(account.as_deref())?;
+    let recipient = account.sender(&provider).await?;
+
+    let expected_amount = quote(&provider, quoter, &route, amount_in)
+        .await
+        .map_err(|e| QuoteFailure::new("uniswap_v3", route.tokens.clone(), e))?;
+    let amount_out_minimum = min_amount_out(expected_amount, params.slippage_bps);
+
+    let deadline = params.deadline();
+    let mut data = Vec::new();
+    if !native.input {
+        match ensure_allowance(
+            &provider,
+            &chain_name,
+            route.input_token(),
+            &account,
+            router,
+            amount_in,
+            deadline,
+        )
+        .await?
+        {
+            Allowance::Ready => {}
+            Allowance::Permit(call) => data.push(call),
+            Allowance::Pending(execution) => return Ok(execution),
+        }
+    }
+    data.extend(swap_calls(
+        &route,
+        native,
+        amount_in,
+        amount_out_minimum,
+        recipient,
+    ));
+
+    let value = if native.input { amount_in } else { U256::ZERO };
+    let tx = create_swap_request(router, data, value, deadline);
+    let input_token = if native.input {
+        Address::ZERO
+    } else {
+        route.input_token()
+    };
+    let output_token = if native.output {
+        Address::ZERO
+    } else {
+        route.output_token()
+    };
+    let changes = vec![
+        BalanceChange::outgoing(input_token, amount_in),
+        BalanceChange::incoming(output_token, expected_amount),
+    ];
+    // Send the transaction and return the transaction hash
+    execute(tx, provider, ctx, changes).await.map_err(rpc_error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_path() {
+        let a = Address::repeat_byte(0xaa);
+        let b = Address::repeat_byte(0xbb);
+        let c = Address::repeat_byte(0xcc);
+        let route = SwapRoute {
+            tokens: vec![a, b, c],
+            fees: vec![500, 3000],
+        };
+
+        let path = route.encode_path();
+        assert_eq!(path.len(), 20 + 23 * 2);
+        assert_eq!(&path[..20], a.as_slice());
+        assert_eq!(&path[20..23], &[0x00, 0x01, 0xf4]);
+        assert_eq!(&path[23..43], b.as_slice());
+        assert_eq!(&path[43..46], &[0x00, 0x0b, 0xb8]);
+        assert_eq!(&path[46..], c.as_slice());
+    }
+
+    #[test]
+    fn test_candidate_routes() {
+        let input = Address::repeat_byte(0x01);
+        let output = Address::repeat_byte(0x02);
+        let weth = Address::repeat_byte(0x03);
+
+        // The output token itself is never used as an intermediate.
+        let routes = candidate_routes(input, output, &[weth, output]);
+        assert_eq!(
+            routes.len(),
+            FEE_TIERS.len() + FEE_TIERS.len() * FEE_TIERS.len()
+        );
+        assert!(routes.contains(&SwapRoute::single(input, output, 500)));
+        assert!(routes.contains(&SwapRoute {
+            tokens: vec![input, weth, output],
+            fees: vec![3000, 100],
+        }));
+    }
+
+    #[test]
+    fn test_native_output_unwraps() {
+        let weth = Address::repeat_byte(0xee);
+        let usdc = Address::repeat_byte(0x01);
+        let router = Address::repeat_byte(0x02);
+        let recipient = Address::repeat_byte(0x03);
+        let route = SwapRoute::single(usdc, weth, 500);
+        let native = NativeEth::of(usdc, Address::ZERO);
+
+        let data = swap_calls(
+            &route,
+            native,
+            U256::from(1_000u64),
+            U256::from(900u64),
+            recipient,
+        );
+        let request = create_swap_request(router, data, U256::ZERO, U256::from(1u64));
+        let input = request.input.input().unwrap();
+        let multicall = ISwapRouter02::multicallCall::abi_decode(input, true).unwrap();
+        assert_eq!(multicall.data.len(), 2);
+        let swap = ISwapRouter02::exactInputSingleCall::abi_decode(&multicall.data[0], true)
+            .unwrap()
+            .params;
+        assert_eq!(swap.recipient, ADDRESS_THIS);
+        let unwrap = ISwapRouter02::unwrapWETH9Call::abi_decode(&multicall.data[1], true).unwrap();
+        assert_eq!(unwrap.recipient, recipient);
+        assert_eq!(unwrap.amountMinimum, U256::from(900u64));
+        assert!(request.value.is_none());
+    }
+
+    #[test]
+    fn test_min_amount_out() {
+        assert_eq!(
+            min_amount_out(U256::from(1_000_000u64), 50),
+            U256::from(995_000u64)
+        );
+        assert_eq!(min_amount_out(U256::from(1_000u64), 20_000), U256::ZERO);
+    }
+}
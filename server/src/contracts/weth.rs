@@ -0,0 +1,89 @@
+use crate::chains::get_chain_info;
+use crate::wallets::{execute, BalanceChange, Execution, TxContext};
+use alloy::{
+    primitives::{Address, U256},
+    rpc::types::TransactionRequest,
+    sol_types::SolCall,
+};
+use anyhow::anyhow;
+use std::str::FromStr;
+
+use super::{
+    abi::IWETH9,
+    common::{make_provider, rpc_error},
+};
+
+/// WETH address listed in `ChainInfo.tokens` for `chain_name`.
+pub fn weth_address(chain_name: &str) -> std::result::Result<Address, anyhow::Error> {
+    let chain_info =
+        get_chain_info(chain_name).ok_or_else(|| anyhow!("unknown chain: {}", chain_name))?;
+    let weth = chain_info
+        .tokens
+        .get("WETH")
+        .ok_or_else(|| anyhow!("WETH token not found on {}", chain_name))?;
+    Address::from_str(weth).map_err(|_| anyhow!("Invalid WETH address format"))
+}
+
+/// Request wrapping `amount` of native ETH into `weth`.
+pub fn deposit_weth_request(weth: Address, amount: U256) -> TransactionRequest {
+    TransactionRequest::default()
+        .to(weth)
+        .value(amount)
+        .input(IWETH9::depositCall {}.abi_encode().into())
+}
+
+/// Request unwrapping `amount` of `weth` into native ETH.
+pub fn withdraw_weth_request(weth: Address, amount: U256) -> TransactionRequest {
+    TransactionRequest::default()
+        .to(weth)
+        .input(IWETH9::withdrawCall { wad: amount }.abi_encode().into())
+}
+
+/// Wrap `amount` of native ETH into WETH.
+pub async fn deposit_weth(
+    amount: U256,
+    chain_name: String,
+    account: Option<String>,
+) -> std::result::Result<Execution, anyhow::Error> {
+    let weth = weth_address(&chain_name)?;
+    let provider = make_provider(chain_name.clone())?;
+    let request = deposit_weth_request(weth, amount);
+    let ctx = TxContext::new(&chain_name, "wrap_eth").with_account(account);
+    let changes = vec![
+        BalanceChange::outgoing(Address::ZERO, amount),
+        BalanceChange::incoming(weth, amount),
+    ];
+    execute(request, provider, ctx, changes)
+        .await
+        .map_err(rpc_error)
+}
+
+/// Unwrap `amount` of WETH back into native ETH.
+pub async fn withdraw_weth(
+    amount: U256,
+    chain_name: String,
+    account: Option<String>,
+) -> std::result::Result<Execution, anyhow::Error> {
+    let weth = weth_address(&chain_name)?;
+    let provider = make_provider(chain_name.clone())?;
+    let request = withdraw_weth_request(weth, amount);
+    let ctx = TxContext::new(&chain_name, "unwrap_weth").with_account(account);
+    let changes = vec![
+        BalanceChange::outgoing(weth, amount),
+        BalanceChange::incoming(Address::ZERO, amount),
+    ];
+    execute(request, provider, ctx, changes)
+        .await
+        .map_err(rpc_error)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_weth_address() {
+        assert!(weth_address("ethereum").is_ok());
+        assert!(weth_address("unknown").is_err());
+    }
+}
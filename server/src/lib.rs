@@ -1,5 +1,6 @@
 pub mod chains;
 pub mod contracts;
+pub mod policy;
 mod service;
 pub mod tools;
 mod utils;
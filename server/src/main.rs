@@ -1,4 +1,4 @@
-use agent_trade::server;
+use agent_trade::{server, wallets};
 use dotenv::dotenv;
 
 #[tokio::main]
@@ -6,6 +6,17 @@ async fn main() {
     dotenv().ok();
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
+    // Decrypt the keystore once, before any tool can request a signature.
+    if let Err(e) = wallets::keystore::unlock_at_startup() {
+        log::error!("Failed to unlock keystore: {}", e);
+        std::process::exit(1);
+    }
+
     log::info!("Starting server...");
     server::start().await;
+
+    // Wipe the decrypted keys from memory before exiting.
+    wallets::signer::clear_signer();
+    wallets::accounts::clear();
+    log::info!("Server stopped");
 }
@@ -0,0 +1,473 @@
+use std::{collections::HashMap, fs, str::FromStr, sync::Mutex};
+
+use alloy::primitives::Address;
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chains::get_chain_info,
+    utils::DB,
+    wallets::execution::{ExecutionMode, EXECUTION_MODE},
+};
+
+/// sled tree holding authorized spends, used for the rolling windows.
+const SPEND_TREE: &str = "policy_spends";
+/// sled tree holding every policy decision.
+const DECISION_TREE: &str = "policy_decisions";
+/// sled tree holding runtime kill switch overrides per chain.
+const KILL_SWITCH_TREE: &str = "policy_kill_switch";
+
+/// Held from reading the spend history to recording the new spend, so that concurrent
+/// tool calls can't each pass a limit only one of them fits in.
+static AUTHORIZE_LOCK: Mutex<()> = Mutex::new(());
+
+const DAY_SECS: i64 = 24 * 60 * 60;
+const WEEK_SECS: i64 = 7 * DAY_SECS;
+
+/// Per-transaction and rolling limits, in human units of the token (or USD).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Limit {
+    pub per_tx: Option<f64>,
+    pub daily: Option<f64>,
+    pub weekly: Option<f64>,
+}
+
+/// Which transactions are parked for a human decision before signing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Tools whose transactions always need approval.
+    #[serde(default)]
+    pub always: Vec<String>,
+    /// Outgoing amounts above these (human units, per token symbol) need approval.
+    #[serde(default)]
+    pub token_thresholds: HashMap<String, f64>,
+    /// Outgoing USD value above this needs approval, for tokens with a known price.
+    pub usd_threshold: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PolicyConfig {
+    /// Chains on which no transaction may be signed.
+    #[serde(default)]
+    pub disabled_chains: Vec<String>,
+    /// Limits per token symbol (or lowercase address for unlisted tokens).
+    #[serde(default)]
+    pub token_limits: HashMap<String, Limit>,
+    /// Limits applied to tokens without an entry in `token_limits`.
+    #[serde(default)]
+    pub default_limit: Limit,
+    /// Limits on the USD value of spends, for tokens with a known price.
+    #[serde(default)]
+    pub usd_limit: Limit,
+    /// USD prices per token symbol.
+    #[serde(default)]
+    pub usd_prices: HashMap<String, f64>,
+    /// If not empty, only these recipients may receive funds or approvals.
+    #[serde(default)]
+    pub recipient_allowlist: Vec<Address>,
+    #[serde(default)]
+    pub recipient_denylist: Vec<Address>,
+    #[serde(default)]
+    pub approval: ApprovalConfig,
+}
+
+pub static POLICY: Lazy<PolicyConfig> = Lazy::new(|| {
+    let content = fs::read_to_string("configs/policy.json").expect("Failed to read policy.json");
+    serde_json::from_str(&content).expect("Failed to parse policy.json")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActionKind {
+    /// Moves funds out of the account; counted against rolling limits.
+    Spend,
+    /// Grants an allowance; only checked against the per-transaction limit.
+    Approve,
+    /// Wraps or unwraps the native coin, so the value stays in the account; checked like
+    /// an approval.
+    Wrap,
+}
+
+/// An action a tool wants to sign.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Action {
+    pub tool: String,
+    pub chain: String,
+    pub kind: ActionKind,
+    /// Token symbol, or address for tokens not listed in `chains.json`.
+    pub token: String,
+    /// Amount in human units, e.g. 0.5 for 0.5 ETH.
+    pub amount: f64,
+    pub recipient: Option<Address>,
+}
+
+impl Action {
+    pub fn spend(tool: &str, chain: &str, token: &str, amount: f64) -> Self {
+        Self {
+            tool: tool.to_string(),
+            chain: chain.to_string(),
+            kind: ActionKind::Spend,
+            token: token.to_string(),
+            amount,
+            recipient: None,
+        }
+    }
+
+    pub fn approve(tool: &str, chain: &str, token: &str, amount: f64, spender: Address) -> Self {
+        Self {
+            kind: ActionKind::Approve,
+            recipient: Some(spender),
+            ..Self::spend(tool, chain, token, amount)
+        }
+    }
+
+    pub fn wrap(tool: &str, chain: &str, token: &str, amount: f64) -> Self {
+        Self {
+            kind: ActionKind::Wrap,
+            ..Self::spend(tool, chain, token, amount)
+        }
+    }
+
+    pub fn to(mut self, recipient: Address) -> Self {
+        self.recipient = Some(recipient);
+        self
+    }
+}
+
+/// A spend already authorized, kept for the rolling windows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Spend {
+    pub token: String,
+    pub amount: f64,
+    pub usd: Option<f64>,
+    pub timestamp: i64,
+}
+
+/// A logged policy decision.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Decision {
+    pub action: Action,
+    pub allowed: bool,
+    pub reason: String,
+    pub timestamp: i64,
+}
+
+/// Name used for a token in the policy: its symbol on `chain`, or its address.
+pub fn token_name(chain: &str, token: Address) -> String {
+    if token == Address::ZERO {
+        return "ETH".to_string();
+    }
+    get_chain_info(chain)
+        .and_then(|info| {
+            info.tokens
+                .iter()
+                .find(|(_, address)| Address::from_str(address).ok() == Some(token))
+                .map(|(symbol, _)| symbol.clone())
+        })
+        .unwrap_or_else(|| token.to_string().to_lowercase())
+}
+
+fn check_limit(
+    label: &str,
+    limit: &Limit,
+    amount: f64,
+    spent_day: f64,
+    spent_week: f64,
+    kind: ActionKind,
+) -> Result<(), String> {
+    if let Some(max) = limit.per_tx {
+        if amount > max {
+            return Err(format!(
+                "{} amount {} exceeds per-tx limit {}",
+                label, amount, max
+            ));
+        }
+    }
+    if kind != ActionKind::Spend {
+        return Ok(());
+    }
+    if let Some(max) = limit.daily {
+        if spent_day + amount > max {
+            return Err(format!(
+                "{} amount {} exceeds daily limit {} ({} already spent)",
+                label, amount, max, spent_day
+            ));
+        }
+    }
+    if let Some(max) = limit.weekly {
+        if spent_week + amount > max {
+            return Err(format!(
+                "{} amount {} exceeds weekly limit {} ({} already spent)",
+                label, amount, max, spent_week
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Decide whether `action` is allowed, given earlier spends and the kill switch state.
+pub fn evaluate(
+    config: &PolicyConfig,
+    history: &[Spend],
+    action: &Action,
+    chain_disabled: bool,
+    now: i64,
+) -> Result<(), String> {
+    if chain_disabled || config.disabled_chains.contains(&action.chain) {
+        return Err(format!("kill switch is active for chain {}", action.chain));
+    }
+    if !(action.amount.is_finite() && action.amount >= 0.0) {
+        return Err(format!("invalid amount {}", action.amount));
+    }
+
+    if let Some(recipient) = action.recipient {
+        if config.recipient_denylist.contains(&recipient) {
+            return Err(format!("recipient {} is denylisted", recipient));
+        }
+        if !config.recipient_allowlist.is_empty()
+            && !config.recipient_allowlist.contains(&recipient)
+        {
+            return Err(format!("recipient {} is not allowlisted", recipient));
+        }
+    }
+
+    let sum = |window: i64, f: &dyn Fn(&Spend) -> Option<f64>| -> f64 {
+        history
+            .iter()
+            .filter(|s| now - s.timestamp < window)
+            .filter_map(f)
+            .sum()
+    };
+
+    let limit = config
+        .token_limits
+        .get(&action.token)
+        .unwrap_or(&config.default_limit);
+    let token_amount = |s: &Spend| (s.token == action.token).then_some(s.amount);
+    check_limit(
+        &action.token,
+        limit,
+        action.amount,
+        sum(DAY_SECS, &token_amount),
+        sum(WEEK_SECS, &token_amount),
+        action.kind,
+    )?;
+
+    if let Some(price) = config.usd_prices.get(&action.token) {
+        let usd_amount = |s: &Spend| s.usd;
+        check_limit(
+            "USD",
+            &config.usd_limit,
+            action.amount * price,
+            sum(DAY_SECS, &usd_amount),
+            sum(WEEK_SECS, &usd_amount),
+            action.kind,
+        )?;
+    }
+    Ok(())
+}
+
+fn load_history() -> Result<Vec<Spend>, anyhow::Error> {
+    let tree = DB.open_tree(SPEND_TREE)?;
+    let cutoff = chrono::Utc::now().timestamp() - WEEK_SECS;
+    Ok(tree
+        .iter()
+        .values()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| serde_json::from_slice::<Spend>(&v).ok())
+        .filter(|s| s.timestamp >= cutoff)
+        .collect())
+}
+
+/// Whether the runtime kill switch is engaged for `chain`.
+pub fn is_chain_disabled(chain: &str) -> Result<bool, anyhow::Error> {
+    let tree = DB.open_tree(KILL_SWITCH_TREE)?;
+    Ok(tree.get(chain)?.is_some_and(|v| v.as_ref() == [1u8]))
+}
+
+/// Engage or release the runtime kill switch for `chain`.
+pub fn set_kill_switch(chain: &str, disabled: bool) -> Result<(), anyhow::Error> {
+    let tree = DB.open_tree(KILL_SWITCH_TREE)?;
+    tree.insert(chain, vec![disabled as u8])?;
+    log::warn!("policy: kill switch for {} set to {}", chain, disabled);
+    Ok(())
+}
+
+fn log_decision(action: &Action, result: &Result<(), String>) -> Result<(), anyhow::Error> {
+    let decision = Decision {
+        action: action.clone(),
+        allowed: result.is_ok(),
+        reason: result.clone().err().unwrap_or_else(|| "ok".to_string()),
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    if decision.allowed {
+        log::info!("policy: allowed {:?}", decision.action);
+    } else {
+        log::warn!("policy: denied {:?}: {}", decision.action, decision.reason);
+    }
+
+    let tree = DB.open_tree(DECISION_TREE)?;
+    tree.insert(
+        DB.generate_id()?.to_be_bytes().to_vec(),
+        serde_json::to_vec(&decision)?,
+    )?;
+    Ok(())
+}
+
+/// Check `action` against the policy and, for spends in live mode, count it towards the
+/// rolling limits.
+///
+/// Every tool calls this before signing. Dry runs and paper trades are checked but never
+/// move funds, so they don't use up the limits. The returned id can be passed to
+/// `release` if the transaction is not sent after all.
+pub fn authorize(action: &Action) -> Result<Option<u64>, anyhow::Error> {
+    let _guard = AUTHORIZE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let history = load_history()?;
+    let now = chrono::Utc::now().timestamp();
+    let result = evaluate(
+        &POLICY,
+        &history,
+        action,
+        is_chain_disabled(&action.chain)?,
+        now,
+    );
+    log_decision(action, &result)?;
+    result.map_err(|reason| anyhow!("policy denied {}: {}", action.tool, reason))?;
+
+    if action.kind != ActionKind::Spend || *EXECUTION_MODE != ExecutionMode::Live {
+        return Ok(None);
+    }
+    let spend = Spend {
+        token: action.token.clone(),
+        amount: action.amount,
+        usd: POLICY
+            .usd_prices
+            .get(&action.token)
+            .map(|p| p * action.amount),
+        timestamp: now,
+    };
+    let id = DB.generate_id()?;
+    DB.open_tree(SPEND_TREE)?
+        .insert(id.to_be_bytes().to_vec(), serde_json::to_vec(&spend)?)?;
+    Ok(Some(id))
+}
+
+/// Drop spends recorded by `authorize` whose transaction was never sent.
+pub fn release_all(ids: &[u64]) {
+    for id in ids {
+        release(Some(*id));
+    }
+}
+
+/// Drop a spend recorded by `authorize` whose transaction failed before being sent.
+pub fn release(id: Option<u64>) {
+    let Some(id) = id else { return };
+    match DB.open_tree(SPEND_TREE) {
+        Ok(tree) => {
+            if let Err(e) = tree.remove(id.to_be_bytes().to_vec()) {
+                log::error!("policy: release spend {} error: {}", id, e);
+            }
+        }
+        Err(e) => log::error!("policy: release spend {} error: {}", id, e),
+    }
+}
+
+/// The most recent policy decisions, newest first.
+pub fn recent_decisions(limit: usize) -> Result<Vec<Decision>, anyhow::Error> {
+    let tree = DB.open_tree(DECISION_TREE)?;
+    Ok(tree
+        .iter()
+        .rev()
+        .values()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| serde_json::from_slice(&v).ok())
+        .take(limit)
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> PolicyConfig {
+        PolicyConfig {
+            token_limits: HashMap::from([(
+                "ETH".to_string(),
+                Limit {
+                    per_tx: Some(1.0),
+                    daily: Some(2.0),
+                    weekly: Some(5.0),
+                },
+            )]),
+            usd_limit: Limit {
+                per_tx: Some(1000.0),
+                daily: None,
+                weekly: None,
+            },
+            usd_prices: HashMap::from([("USDC".to_string(), 1.0)]),
+            ..Default::default()
+        }
+    }
+
+    fn spend(token: &str, amount: f64, timestamp: i64) -> Spend {
+        Spend {
+            token: token.to_string(),
+            amount,
+            usd: None,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn test_per_tx_and_rolling_limits() {
+        let now = 10 * WEEK_SECS;
+        let action = Action::spend("eth_transfer", "base", "ETH", 0.8);
+        assert!(evaluate(&config(), &[], &action, false, now).is_ok());
+
+        let too_big = Action::spend("eth_transfer", "base", "ETH", 1.5);
+        assert!(evaluate(&config(), &[], &too_big, false, now).is_err());
+
+        // 1.5 ETH spent in the last day plus 0.8 breaks the daily limit of 2.
+        let history = vec![spend("ETH", 1.0, now - 60), spend("ETH", 0.5, now - 3600)];
+        assert!(evaluate(&config(), &history, &action, false, now).is_err());
+
+        // Spends older than a day only count towards the weekly limit.
+        let history = vec![spend("ETH", 1.0, now - 2 * DAY_SECS)];
+        assert!(evaluate(&config(), &history, &action, false, now).is_ok());
+
+        // Wrapping moves nothing out, so only the per-tx limit applies.
+        let history = vec![spend("ETH", 1.5, now - 60)];
+        let wrap = Action::wrap("wrap_eth", "base", "ETH", 0.8);
+        assert!(evaluate(&config(), &history, &wrap, false, now).is_ok());
+        let wrap = Action::wrap("wrap_eth", "base", "ETH", 1.5);
+        assert!(evaluate(&config(), &[], &wrap, false, now).is_err());
+    }
+
+    #[test]
+    fn test_usd_limit_and_recipients() {
+        let now = 10 * WEEK_SECS;
+        let action = Action::spend("erc20_transfer", "base", "USDC", 2000.0);
+        assert!(evaluate(&config(), &[], &action, false, now).is_err());
+
+        let mut config = config();
+        let recipient = Address::repeat_byte(0x11);
+        config.recipient_denylist.push(recipient);
+        let action = Action::spend("erc20_transfer", "base", "USDC", 10.0).to(recipient);
+        assert!(evaluate(&config, &[], &action, false, now).is_err());
+    }
+
+    #[test]
+    fn test_kill_switch() {
+        let action = Action::spend("eth_transfer", "base", "ETH", 0.1);
+        assert!(evaluate(&config(), &[], &action, true, 0).is_err());
+        let wrap = Action::wrap("wrap_eth", "base", "ETH", 0.1);
+        assert!(evaluate(&config(), &[], &wrap, true, 0).is_err());
+
+        let mut config = config();
+        config.disabled_chains.push("base".to_string());
+        assert!(evaluate(&config, &[], &action, false, 0).is_err());
+    }
+}
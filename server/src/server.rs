@@ -3,31 +3,52 @@ use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::time::Duration;
 
+use crate::contracts::common::make_provider;
+use crate::contracts::rpc;
+use crate::policy;
 use crate::service::sns::call_heurist_mesh;
 use crate::service::token::solana::{
     get_ai_signals_paginated, get_meme_tokens_paginated, PaginationParams,
 };
+use crate::wallets::{accounts, approvals, eip7702, paper, safe, tracker, Execution};
+use alloy::primitives::{Bytes, TxHash};
+use alloy::providers::Provider;
 use axum::body::Body;
 use axum::debug_handler;
-use axum::extract::Request;
+use axum::extract::{Query, Request};
+use axum::http::header::AUTHORIZATION;
 use axum::middleware::{self, Next};
 use axum::response::sse::Event;
 use axum::response::{Response, Sse};
 use axum::{extract::Json, http::StatusCode, response::IntoResponse};
-use axum::{routing::post, Router};
+use axum::{
+    routing::{get, post},
+    Router,
+};
 use futures::stream::{self, Stream};
 use futures::StreamExt;
+use once_cell::sync::Lazy;
 use rig::{completion::Prompt, providers::openai};
 use serde::{Deserialize, Serialize};
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::utils::read_parse_env;
 
+/// Bearer token of the routes acting on the account's keys; they are closed when unset.
+static ADMIN_API_TOKEN: Lazy<Option<String>> = Lazy::new(|| {
+    std::env::var("ADMIN_API_TOKEN")
+        .ok()
+        .filter(|token| !token.is_empty())
+});
+
 // Request data structure
 #[derive(Debug, Deserialize)]
 pub struct AgentRequest {
     context: String,
     msg: String,
+    // Named account the session acts for; the default account when unset.
+    #[serde(default)]
+    account: Option<String>,
 }
 
 // Response data structure
@@ -70,6 +91,11 @@ struct Delta {
 }
 
 pub async fn start() {
+    // Watch submitted transactions for inclusion and stuck fees.
+    tracker::start_tracker();
+    // Probe RPC endpoints so calls fail over away from lagging or dead ones.
+    rpc::start_health_checks();
+
     let app = create_router();
     // Create CORS middleware
     let cors = CorsLayer::new()
@@ -83,13 +109,51 @@ pub async fn start() {
     log::info!("Server running on http://{}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app)
+        .with_graceful_shutdown(shutdown_signal())
+        .await
+        .unwrap();
+}
+
+// Resolves on Ctrl+C or SIGTERM (docker/systemd stop) so the caller can clean up secrets
+// before exiting.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            log::error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                sigterm.recv().await;
+            }
+            Err(e) => {
+                log::error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    log::info!("Shutdown signal received");
 }
 
 async fn logging_middleware(req: Request, next: Next) -> Response {
     println!("Method: {:?}", req.method());
     println!("URI: {:?}", req.uri());
-    println!("Headers: {:?}", req.headers());
+    let mut headers = req.headers().clone();
+    headers.remove(AUTHORIZATION);
+    println!("Headers: {:?}", headers);
 
     // If request body needs to be inspected
     let (parts, body) = req.into_parts();
@@ -119,9 +183,459 @@ pub fn create_router() -> Router {
         .route("/agent/chat/completions", post(chat_stream))
         .route("/token/ai_signals", post(handle_ai_signals))
         .route("/token/meme_tokens", post(handle_meme_tokens))
+        .route("/txs/pending", get(handle_pending_txs))
+        .route("/txs/speed_up", post(handle_speed_up_tx))
+        .route("/txs/cancel", post(handle_cancel_tx))
+        .route("/paper/fills", get(handle_paper_fills))
+        .route("/policy/decisions", get(handle_policy_decisions))
+        .route("/policy/kill_switch", post(handle_kill_switch))
+        .route("/approvals", get(handle_pending_approvals))
+        .route("/approvals/approve", post(handle_approve))
+        .route("/approvals/reject", post(handle_reject))
+        .route("/accounts", get(handle_accounts))
+        .route("/chains/status", get(handle_chain_status))
+        .route("/safe/proposals", get(handle_safe_proposals))
+        .route("/safe/confirm", post(handle_safe_confirm))
+        .route("/safe/sign", post(handle_safe_sign))
+        .route("/safe/execute", post(handle_safe_execute))
+        .route("/safe/reject", post(handle_safe_reject))
+        .merge(admin_router())
         .layer(middleware::from_fn(logging_middleware))
 }
 
+// Routes that sign or send with the account's key, behind `admin_auth`
+fn admin_router() -> Router {
+    Router::new()
+        .route("/eip7702/delegation", get(handle_delegation))
+        .route("/eip7702/authorization", post(handle_sign_authorization))
+        .route("/eip7702/delegate", post(handle_delegate))
+        .route("/eip7702/revoke", post(handle_revoke))
+        .route_layer(middleware::from_fn(admin_auth))
+}
+
+// Let a request through only with `Authorization: Bearer $ADMIN_API_TOKEN`
+async fn admin_auth(req: Request, next: Next) -> Result<Response, StatusCode> {
+    let Some(token) = ADMIN_API_TOKEN.as_deref() else {
+        log::warn!("{} refused: ADMIN_API_TOKEN is not set", req.uri());
+        return Err(StatusCode::FORBIDDEN);
+    };
+    let authorized = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|bearer| same_token(bearer.as_bytes(), token.as_bytes()));
+    if !authorized {
+        log::warn!("{} refused: missing or wrong admin token", req.uri());
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(req).await)
+}
+
+// Compare tokens in a time that doesn't depend on where they differ
+fn same_token(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// Request structure for tx replacement
+#[derive(Debug, Deserialize)]
+pub struct TxHashRequest {
+    hash: TxHash,
+}
+
+// Handler listing transactions that are not yet included
+pub async fn handle_pending_txs() -> Result<impl IntoResponse, StatusCode> {
+    match tracker::list_pending() {
+        Ok(txs) => Ok((StatusCode::OK, Json(txs))),
+        Err(e) => {
+            log::error!("Error listing pending txs: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Handler re-sending a pending tx with bumped fees
+pub async fn handle_speed_up_tx(
+    Json(payload): Json<TxHashRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Speed up request: hash={}", payload.hash);
+
+    match tracker::speed_up(payload.hash).await {
+        Ok(hash) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: hash.to_string(),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error speeding up tx {}: {}", payload.hash, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler replacing a pending tx with a zero-value self transfer
+pub async fn handle_cancel_tx(
+    Json(payload): Json<TxHashRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Cancel request: hash={}", payload.hash);
+
+    match tracker::cancel(payload.hash).await {
+        Ok(hash) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: hash.to_string(),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error cancelling tx {}: {}", payload.hash, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler listing simulated trades recorded in paper mode
+pub async fn handle_paper_fills() -> Result<impl IntoResponse, StatusCode> {
+    match paper::list_fills() {
+        Ok(fills) => Ok((StatusCode::OK, Json(fills))),
+        Err(e) => {
+            log::error!("Error listing paper fills: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Request structure for the per-chain kill switch
+#[derive(Debug, Deserialize)]
+pub struct KillSwitchRequest {
+    chain: String,
+    disabled: bool,
+}
+
+// Handler listing the latest policy decisions
+pub async fn handle_policy_decisions() -> Result<impl IntoResponse, StatusCode> {
+    match policy::recent_decisions(100) {
+        Ok(decisions) => Ok((StatusCode::OK, Json(decisions))),
+        Err(e) => {
+            log::error!("Error listing policy decisions: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Handler engaging or releasing the kill switch of a chain
+pub async fn handle_kill_switch(
+    Json(payload): Json<KillSwitchRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    match policy::set_kill_switch(&payload.chain, payload.disabled) {
+        Ok(()) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: format!("{} disabled={}", payload.chain, payload.disabled),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error setting kill switch for {}: {}", payload.chain, e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Request structure for deciding on a parked transaction
+#[derive(Debug, Deserialize)]
+pub struct ApprovalRequest {
+    id: u64,
+    reason: Option<String>,
+}
+
+// Handler listing transactions waiting for user approval
+pub async fn handle_pending_approvals() -> Result<impl IntoResponse, StatusCode> {
+    match approvals::list_pending() {
+        Ok(actions) => Ok((StatusCode::OK, Json(actions))),
+        Err(e) => {
+            log::error!("Error listing pending approvals: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Handler approving a parked transaction, which signs and sends it
+pub async fn handle_approve(
+    Json(payload): Json<ApprovalRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Approve request: id={}", payload.id);
+
+    match approvals::approve(payload.id).await {
+        Ok(receipt) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: receipt.transaction_hash.to_string(),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error approving #{}: {}", payload.id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler rejecting a parked transaction
+pub async fn handle_reject(
+    Json(payload): Json<ApprovalRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Reject request: id={}", payload.id);
+
+    match approvals::reject(payload.id, payload.reason) {
+        Ok(action) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: format!("#{} rejected", action.id),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error rejecting #{}: {}", payload.id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler listing the configured accounts and their addresses
+pub async fn handle_accounts() -> impl IntoResponse {
+    (StatusCode::OK, Json(accounts::list()))
+}
+
+// Handler showing the health of every chain's RPC endpoints
+pub async fn handle_chain_status() -> Result<impl IntoResponse, StatusCode> {
+    match rpc::chain_statuses() {
+        Ok(statuses) => Ok((StatusCode::OK, Json(statuses))),
+        Err(e) => {
+            log::error!("Error reading chain status: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Request structure naming the chain and account of an EIP-7702 operation
+#[derive(Debug, Deserialize)]
+pub struct ChainRequest {
+    chain: String,
+    // Defaults to the default account.
+    account: Option<String>,
+}
+
+// Request structure for signing an EIP-7702 authorization
+#[derive(Debug, Deserialize)]
+pub struct AuthorizationRequest {
+    chain: String,
+    // Defaults to the default account.
+    account: Option<String>,
+    // Defaults to the account's current nonce, for a transaction sent by a sponsor.
+    nonce: Option<u64>,
+}
+
+// Handler returning the current delegate of an account
+pub async fn handle_delegation(
+    Query(payload): Query<ChainRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let result = async {
+        let provider = make_provider(payload.chain.clone())?;
+        let account = accounts::account(payload.account.as_deref())?.address();
+        let delegate = eip7702::current_delegation(&provider, account).await?;
+        Ok::<_, anyhow::Error>(serde_json::json!({
+            "account": account,
+            "delegate": delegate,
+        }))
+    }
+    .await;
+
+    match result {
+        Ok(delegation) => Ok((StatusCode::OK, Json(delegation))),
+        Err(e) => {
+            log::error!("Error reading delegation on {}: {}", payload.chain, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler signing an authorization list entry without sending it
+pub async fn handle_sign_authorization(
+    Json(payload): Json<AuthorizationRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let result = async {
+        let provider = make_provider(payload.chain.clone())?;
+        let account = accounts::account(payload.account.as_deref())?;
+        // Only the configured implementation is ever signed for.
+        let implementation = (*eip7702::DELEGATE_IMPLEMENTATION)
+            .ok_or_else(|| anyhow::anyhow!("EIP7702_IMPLEMENTATION is not set"))?;
+        let nonce = match payload.nonce {
+            Some(nonce) => nonce,
+            None => provider.get_transaction_count(account.address()).await?,
+        };
+        let chain_id = provider.get_chain_id().await?;
+        eip7702::sign_authorization(&account.signer, chain_id, implementation, nonce).await
+    }
+    .await;
+
+    match result {
+        Ok(authorization) => Ok((StatusCode::OK, Json(authorization))),
+        Err(e) => {
+            log::error!("Error signing authorization on {}: {}", payload.chain, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler delegating an account to the smart-account implementation
+pub async fn handle_delegate(
+    Json(payload): Json<ChainRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Delegate request: chain={}", payload.chain);
+
+    let result = async {
+        let provider = make_provider(payload.chain.clone())?;
+        let account = accounts::account(payload.account.as_deref())?;
+        eip7702::delegate(&provider, &payload.chain, &account).await
+    }
+    .await;
+    delegation_response(&payload.chain, result)
+}
+
+// Handler clearing the delegation of an account
+pub async fn handle_revoke(
+    Json(payload): Json<ChainRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Revoke delegation request: chain={}", payload.chain);
+
+    let result = async {
+        let provider = make_provider(payload.chain.clone())?;
+        let account = accounts::account(payload.account.as_deref())?;
+        eip7702::revoke(&provider, &payload.chain, &account).await
+    }
+    .await;
+    delegation_response(&payload.chain, result)
+}
+
+fn delegation_response(
+    chain: &str,
+    result: Result<Execution, anyhow::Error>,
+) -> Result<(StatusCode, Json<AgentResponse>), StatusCode> {
+    match result {
+        Ok(execution) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: execution.to_string(),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error updating delegation on {}: {}", chain, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Request structure naming a Safe proposal
+#[derive(Debug, Deserialize)]
+pub struct SafeProposalRequest {
+    id: u64,
+}
+
+// Request structure rejecting a Safe proposal
+#[derive(Debug, Deserialize)]
+pub struct SafeRejectRequest {
+    id: u64,
+    reason: Option<String>,
+}
+
+// Request structure carrying an owner's signature over a Safe tx hash
+#[derive(Debug, Deserialize)]
+pub struct SafeConfirmRequest {
+    id: u64,
+    signature: Bytes,
+}
+
+// Handler listing Safe proposals waiting for confirmations
+pub async fn handle_safe_proposals() -> Result<impl IntoResponse, StatusCode> {
+    match safe::list_pending() {
+        Ok(proposals) => Ok((StatusCode::OK, Json(proposals))),
+        Err(e) => {
+            log::error!("Error listing Safe proposals: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Handler storing an owner's confirmation, which executes the proposal at threshold
+pub async fn handle_safe_confirm(
+    Json(payload): Json<SafeConfirmRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Safe confirm request: id={}", payload.id);
+
+    match safe::confirm(payload.id, payload.signature).await {
+        Ok(proposal) => Ok((StatusCode::OK, Json(proposal))),
+        Err(e) => {
+            log::error!("Error confirming Safe proposal #{}: {}", payload.id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler confirming a proposal with the agent's signer
+pub async fn handle_safe_sign(
+    Json(payload): Json<SafeProposalRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Safe sign request: id={}", payload.id);
+
+    match safe::sign(payload.id).await {
+        Ok(proposal) => Ok((StatusCode::OK, Json(proposal))),
+        Err(e) => {
+            log::error!("Error signing Safe proposal #{}: {}", payload.id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler executing a proposal that has enough confirmations
+pub async fn handle_safe_execute(
+    Json(payload): Json<SafeProposalRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Safe execute request: id={}", payload.id);
+
+    match safe::execute(payload.id).await {
+        Ok(receipt) => Ok((
+            StatusCode::OK,
+            Json(AgentResponse {
+                data: receipt.transaction_hash.to_string(),
+                status: "success".to_string(),
+            }),
+        )),
+        Err(e) => {
+            log::error!("Error executing Safe proposal #{}: {}", payload.id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+// Handler proposing a rejection that uses up the nonce of a Safe proposal
+pub async fn handle_safe_reject(
+    Json(payload): Json<SafeRejectRequest>,
+) -> Result<impl IntoResponse, StatusCode> {
+    log::info!("Safe reject request: id={}", payload.id);
+
+    match safe::reject(payload.id, payload.reason).await {
+        Ok(proposal) => Ok((StatusCode::OK, Json(proposal))),
+        Err(e) => {
+            log::error!("Error rejecting Safe proposal #{}: {}", payload.id, e);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
 // Handler for AI signals pagination
 pub async fn handle_ai_signals(
     Json(payload): Json<TokenPaginationRequest>,
@@ -254,8 +768,15 @@ pub async fn handle_agent_prompt(
         return Err(StatusCode::UNAUTHORIZED);
     }
 
+    // Every tool call of this request acts for the requested account.
+    if let Err(e) = accounts::account(payload.account.as_deref()) {
+        log::error!("Rejected agent request: {}", e);
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     // Handling Requests
-    match process_agent_request(&payload).await {
+    let session = accounts::with_session(payload.account.clone(), process_agent_request(&payload));
+    match session.await {
         Ok(result) => {
             let response = AgentResponse {
                 data: result,
@@ -1,7 +1,7 @@
 use crate::service::data::dex::raydium::{query_raydium_pools, PoolInfo};
+use crate::utils::DB;
 use anyhow::Result;
 use once_cell::sync::Lazy;
-use sled;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
@@ -58,8 +58,8 @@ pub fn analyze_pools() -> Result<Vec<PoolInfo>> {
 /// 3. Adds new pools to FILTERED_POOLS if they don't already exist
 /// 4. Saves the updated FILTERED_POOLS back to sled database
 pub fn start_pool_analysis_service() -> Result<()> {
-    // Open the sled database
-    let db = sled::open("agent_trade_db")?;
+    // Use the shared sled database
+    let db = DB.clone();
 
     // Load existing data from sled into FILTERED_POOLS if available
     if let Some(existing_data) = db.get("filtered_pools")? {
@@ -144,11 +144,8 @@ pub fn query_filtered_pools(page_num: usize, page_size: usize) -> Result<(Vec<Po
 
     // If FILTERED_POOLS is empty, try to load from sled database
     if filtered_pools.is_empty() {
-        // Open the sled database
-        let db = sled::open("agent_trade_db")?;
-
         // Load existing data from sled into FILTERED_POOLS if available
-        if let Some(existing_data) = db.get("filtered_pools")? {
+        if let Some(existing_data) = DB.get("filtered_pools")? {
             if let Ok(pools) = serde_json::from_slice::<Vec<PoolInfo>>(&existing_data) {
                 *filtered_pools = pools;
                 println!(
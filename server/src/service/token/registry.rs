@@ -0,0 +1,205 @@
+use std::{collections::HashMap, str::FromStr, sync::RwLock};
+
+use alloy::primitives::Address;
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::{chains::get_chain_info, contracts::erc20::metadata_erc20};
+
+/// Where a registry entry comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenSource {
+    /// The native coin of the chain.
+    Native,
+    /// Listed in `configs/chains.json`.
+    Config,
+    /// Only known from its on-chain metadata.
+    OnChain,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub chain: String,
+    pub symbol: String,
+    pub name: Option<String>,
+    pub address: Address,
+    pub decimals: Option<u8>,
+    pub source: TokenSource,
+}
+
+/// On-chain metadata fetched so far, per `(chain, address)`.
+static METADATA: Lazy<RwLock<HashMap<(String, Address), TokenInfo>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn native(chain: &str) -> TokenInfo {
+    TokenInfo {
+        chain: chain.to_string(),
+        symbol: "ETH".to_string(),
+        name: Some("Ether".to_string()),
+        address: Address::ZERO,
+        decimals: Some(18),
+        source: TokenSource::Native,
+    }
+}
+
+/// `(symbol, address)` pairs configured for `chain`.
+pub(crate) fn configured(chain: &str) -> Vec<(String, Address)> {
+    get_chain_info(chain)
+        .map(|info| {
+            info.tokens
+                .iter()
+                .filter_map(|(symbol, address)| {
+                    Address::from_str(address)
+                        .ok()
+                        .map(|address| (symbol.clone(), address))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Complete an entry with on-chain `name()`/`symbol()`/`decimals()`, caching the result.
+///
+/// Configured symbols win over on-chain ones; if the RPC is unreachable the entry is
+/// returned as is.
+async fn with_metadata(mut info: TokenInfo) -> TokenInfo {
+    let key = (info.chain.clone(), info.address);
+    let cached = METADATA.read().unwrap().get(&key).cloned();
+    let fetched = match cached {
+        Some(fetched) => Some(fetched),
+        None => match metadata_erc20(info.address, info.chain.clone()).await {
+            Ok((name, symbol, decimals)) => {
+                let fetched = TokenInfo {
+                    chain: info.chain.clone(),
+                    symbol,
+                    name: Some(name),
+                    address: info.address,
+                    decimals: Some(decimals),
+                    source: TokenSource::OnChain,
+                };
+                METADATA.write().unwrap().insert(key, fetched.clone());
+                Some(fetched)
+            }
+            Err(e) => {
+                log::warn!(
+                    "token metadata of {} on {} unavailable: {}",
+                    info.address,
+                    info.chain,
+                    e
+                );
+                None
+            }
+        },
+    };
+
+    if let Some(fetched) = fetched {
+        if info.source != TokenSource::Config {
+            info.symbol = fetched.symbol;
+        }
+        info.name = fetched.name;
+        info.decimals = fetched.decimals;
+    }
+    info
+}
+
+/// Resolve a symbol (case-insensitive) or an address to a token on `chain`.
+///
+/// Symbols are only looked up among the native coin and configured tokens.
+pub async fn resolve(chain: &str, query: &str) -> Result<TokenInfo, anyhow::Error> {
+    if get_chain_info(chain).is_none() {
+        return Err(anyhow!("unknown chain: {}", chain));
+    }
+    let query = query.trim();
+    if query.eq_ignore_ascii_case("ETH") {
+        return Ok(native(chain));
+    }
+
+    if let Ok(address) = Address::from_str(query) {
+        let info = match configured(chain).into_iter().find(|(_, a)| *a == address) {
+            Some((symbol, _)) => TokenInfo {
+                chain: chain.to_string(),
+                symbol,
+                name: None,
+                address,
+                decimals: None,
+                source: TokenSource::Config,
+            },
+            None => TokenInfo {
+                chain: chain.to_string(),
+                symbol: address.to_string(),
+                name: None,
+                address,
+                decimals: None,
+                source: TokenSource::OnChain,
+            },
+        };
+        let info = with_metadata(info).await;
+        if info.source == TokenSource::OnChain && info.decimals.is_none() {
+            return Err(anyhow!("{} is not an ERC20 token on {}", address, chain));
+        }
+        return Ok(info);
+    }
+
+    if let Some((symbol, address)) = configured(chain)
+        .into_iter()
+        .find(|(symbol, _)| symbol.eq_ignore_ascii_case(query))
+    {
+        return Ok(with_metadata(TokenInfo {
+            chain: chain.to_string(),
+            symbol,
+            name: None,
+            address,
+            decimals: None,
+            source: TokenSource::Config,
+        })
+        .await);
+    }
+
+    // Symbols of discovered tokens are whatever their contract says, so anyone can deploy
+    // a token named like a configured one; those are only reachable by address.
+    Err(anyhow!("unknown token {} on {}", query, chain))
+}
+
+/// Address of a symbol or address on `chain`, without fetching metadata for addresses.
+pub async fn resolve_address(chain: &str, query: &str) -> Result<Address, anyhow::Error> {
+    if let Ok(address) = Address::from_str(query.trim()) {
+        return Ok(address);
+    }
+    Ok(resolve(chain, query).await?.address)
+}
+
+/// Every token known on `chain`: the native coin, configured tokens and discovered ones.
+pub async fn list(chain: &str) -> Result<Vec<TokenInfo>, anyhow::Error> {
+    if get_chain_info(chain).is_none() {
+        return Err(anyhow!("unknown chain: {}", chain));
+    }
+
+    let mut tokens = vec![native(chain)];
+    for (symbol, address) in configured(chain) {
+        tokens.push(
+            with_metadata(TokenInfo {
+                chain: chain.to_string(),
+                symbol,
+                name: None,
+                address,
+                decimals: None,
+                source: TokenSource::Config,
+            })
+            .await,
+        );
+    }
+
+    let discovered: Vec<TokenInfo> = METADATA
+        .read()
+        .unwrap()
+        .values()
+        .filter(|info| {
+            info.chain == chain && !tokens.iter().any(|known| known.address == info.address)
+        })
+        .cloned()
+        .collect();
+    tokens.extend(discovered);
+    Ok(tokens)
+}
@@ -1,11 +1,16 @@
-use crate::contracts::{erc20::*, eth::*};
+use super::{account_param, erc20_param, tool_error};
+use crate::contracts::{
+    amount::TokenAmount, common::make_provider, erc20::*, eth::*, portfolio::portfolio,
+};
+use crate::wallets::accounts;
 use alloy::primitives::Address;
 use anyhow::Result;
+use futures::future::join_all;
 use rig_derive::rig_tool;
 use std::str::FromStr;
 
 #[rig_tool(
-    description = "Query ETH balance for an account",
+    description = "Query ETH balance for an account, in ETH",
     params(
         chain = "The chain name, such as arbitrum",
         account_address = "The address of the account to query balance for"
@@ -25,7 +30,7 @@ pub async fn get_eth_balance(
 
     let result = eth_balance(account_address, chain).await;
     match result {
-        Ok(balance) => Ok(balance.to_string()),
+        Ok(balance) => Ok(TokenAmount::new(Address::ZERO, balance, 18).to_string()),
         Err(e) => Err(rig::tool::ToolError::ToolCallError(
             format!("get_eth_balance error: {}", e).into(),
         )),
@@ -33,10 +38,10 @@ pub async fn get_eth_balance(
 }
 
 #[rig_tool(
-    description = "Query ERC20 token balance for an account",
+    description = "Query ERC20 token balance for an account, in whole tokens",
     params(
         chain = "The chain name, such as arbitrum",
-        token_address = "The address of the ERC20 token contract",
+        token_address = "Symbol (e.g. USDC) or contract address of the ERC20 token",
         account_address = "The address of the account to query balance for"
     )
 )]
@@ -45,8 +50,7 @@ pub async fn get_balance(
     token_address: String,
     account_address: String,
 ) -> Result<String, rig::tool::ToolError> {
-    let token_address = Address::from_str(&token_address)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid token_address format".into()))?;
+    let token_address = erc20_param(&chain, "token_address", &token_address).await?;
 
     let account_address = Address::from_str(&account_address)
         .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid account_address format".into()))?;
@@ -56,11 +60,69 @@ pub async fn get_balance(
         chain, token_address, account_address
     );
 
-    let result = balance_of_erc20(account_address, token_address, chain).await;
+    let result = balance_of_erc20(account_address, token_address, chain.clone()).await;
     match result {
-        Ok(balance) => Ok(balance.to_string()),
+        Ok(balance) => TokenAmount::from_raw(token_address, balance, &chain)
+            .await
+            .map(|amount| amount.to_string())
+            .map_err(|e| {
+                rig::tool::ToolError::ToolCallError(format!("decimals error: {}", e).into())
+            }),
         Err(e) => Err(rig::tool::ToolError::ToolCallError(
             format!("balance_of_erc20 error: {}", e).into(),
         )),
     }
 }
+
+#[rig_tool(
+    description = "Query the ETH and configured token balances of an account on one or more chains, in whole tokens",
+    params(
+        chains = "Comma-separated chain names, such as arbitrum,base",
+        account_address = "Optional address to query; defaults to the session's account"
+    )
+)]
+pub async fn get_portfolio(
+    chains: String,
+    account_address: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let account_address = match account_address {
+        Some(address) => Some(Address::from_str(&address).map_err(|_| {
+            rig::tool::ToolError::ToolCallError("Invalid account_address format".into())
+        })?),
+        None => None,
+    };
+    let account = accounts::account(account_param(None)?.as_deref())
+        .map_err(|e| tool_error("get_portfolio", e))?;
+
+    log::info!("chains: {}, account_address: {:?}", chains, account_address);
+
+    // One Multicall3 round trip per chain, all chains at once.
+    let chains: Vec<&str> = chains
+        .split(',')
+        .map(str::trim)
+        .filter(|chain| !chain.is_empty())
+        .collect();
+    let results = join_all(chains.iter().map(|chain| {
+        let account = &account;
+        async move {
+            // The session's smart account, for ERC-4337 accounts, is looked up per chain.
+            let address = match account_address {
+                Some(address) => address,
+                None => account.sender(&make_provider(chain.to_string())?).await?,
+            };
+            Ok::<_, anyhow::Error>((address, portfolio(address, chain).await?))
+        }
+    }))
+    .await;
+    let portfolios: Vec<serde_json::Value> = chains
+        .into_iter()
+        .zip(results)
+        .map(|(chain, result)| match result {
+            Ok((account, holdings)) => {
+                serde_json::json!({ "chain": chain, "account": account, "balances": holdings })
+            }
+            Err(e) => serde_json::json!({ "chain": chain, "error": e.to_string() }),
+        })
+        .collect();
+    Ok(serde_json::json!({ "chains": portfolios }).to_string())
+}
@@ -0,0 +1,288 @@
+use super::{account_param, authorize, erc20_param, settle, token_param, tool_error};
+use crate::contracts::{
+    amount::TokenAmount,
+    common::{make_provider, SwapParams},
+    erc20::{approve_erc20_request, transfer_erc20_request},
+    eth::transfer_eth_request,
+    swap::swap_v2_request,
+    weth::{deposit_weth_request, weth_address, withdraw_weth_request},
+};
+use crate::policy::{self, token_name, Action};
+use crate::wallets::{accounts, eip7702::execute_batch, BalanceChange, TxContext};
+use alloy::{primitives::Address, providers::RootProvider, rpc::types::TransactionRequest};
+use anyhow::Result;
+use rig_derive::rig_tool;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// One step of a batch, in the same terms as the single-action tools.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+enum BatchAction {
+    EthTransfer {
+        to_address: String,
+        amount: String,
+    },
+    Erc20Transfer {
+        token: String,
+        to_address: String,
+        amount: String,
+    },
+    Approve {
+        token: String,
+        spender_address: String,
+        amount: String,
+    },
+    WrapEth {
+        amount: String,
+    },
+    UnwrapWeth {
+        amount: String,
+    },
+    /// Swap through the chain's V2 router, approving it first for ERC20 input.
+    Swap {
+        input_token: String,
+        output_token: String,
+        amount: String,
+        #[serde(default)]
+        fee_on_transfer: bool,
+        slippage_bps: Option<u64>,
+    },
+}
+
+/// Calls, expected balance changes and authorized spends collected for a batch.
+#[derive(Default)]
+struct Bundle {
+    requests: Vec<TransactionRequest>,
+    changes: Vec<BalanceChange>,
+    spends: Vec<Option<u64>>,
+}
+
+impl Bundle {
+    fn release(self) {
+        self.spends.into_iter().for_each(policy::release);
+    }
+}
+
+#[rig_tool(
+    description = "Execute several actions atomically in one transaction through the EIP-7702 smart account; either all of them succeed or none does",
+    params(
+        chain = "The chain name, such as arbitrum",
+        actions = "JSON array of actions, each an object with an `action` field: eth_transfer {to_address, amount}, erc20_transfer {token, to_address, amount}, approve {token, spender_address, amount}, wrap_eth {amount}, unwrap_weth {amount}, swap {input_token, output_token, amount, fee_on_transfer?, slippage_bps?}. Tokens are symbols (ETH for native ETH) or addresses, amounts are in whole tokens",
+        account = "Optional named account to act for; defaults to the session's account"
+    )
+)]
+pub async fn batch_execute(
+    chain: String,
+    actions: String,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let actions: Vec<BatchAction> = serde_json::from_str(&actions).map_err(|e| {
+        rig::tool::ToolError::ToolCallError(format!("Invalid actions: {}", e).into())
+    })?;
+
+    log::info!("chain_name: {}, actions: {:?}", chain, actions);
+
+    let account = account_param(account)?;
+    let provider = make_provider(chain.clone()).map_err(|e| tool_error("provider", e))?;
+    let owner = accounts::account(account.as_deref())
+        .map_err(|e| tool_error("account", e))?
+        .sender(&provider)
+        .await
+        .map_err(|e| tool_error("account", e))?;
+    let mut bundle = Bundle::default();
+    for action in actions {
+        if let Err(e) = add_action(&mut bundle, &provider, &chain, owner, action).await {
+            bundle.release();
+            return Err(e);
+        }
+    }
+
+    let ctx = TxContext::new(&chain, "batch_execute").with_account(account);
+    let result = execute_batch(bundle.requests, provider, ctx, bundle.changes).await;
+    settle("batch_execute", bundle.spends, &result);
+    match result {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(tool_error("batch_execute", e)),
+    }
+}
+
+fn address_param(param: &str, value: &str) -> Result<Address, rig::tool::ToolError> {
+    Address::from_str(value).map_err(|_| {
+        rig::tool::ToolError::ToolCallError(format!("Invalid {} format", param).into())
+    })
+}
+
+// Check one action against the policy and append its call to the bundle; swap output
+// goes to `owner`, the account running the batch.
+async fn add_action(
+    bundle: &mut Bundle,
+    provider: &RootProvider,
+    chain: &str,
+    owner: Address,
+    action: BatchAction,
+) -> Result<(), rig::tool::ToolError> {
+    match action {
+        BatchAction::EthTransfer { to_address, amount } => {
+            let to_address = address_param("to_address", &to_address)?;
+            let amount = TokenAmount::parse(Address::ZERO, &amount, chain)
+                .await
+                .map_err(|e| tool_error("amount", e))?;
+            let action = Action::spend("batch_execute", chain, "ETH", amount.as_f64());
+            bundle.spends.push(authorize(action.to(to_address))?);
+            bundle
+                .requests
+                .push(transfer_eth_request(to_address, amount.raw));
+            bundle
+                .changes
+                .push(BalanceChange::outgoing(Address::ZERO, amount.raw));
+        }
+        BatchAction::Erc20Transfer {
+            token,
+            to_address,
+            amount,
+        } => {
+            let token = erc20_param(chain, "token", &token).await?;
+            let to_address = address_param("to_address", &to_address)?;
+            let amount = TokenAmount::parse(token, &amount, chain)
+                .await
+                .map_err(|e| tool_error("amount", e))?;
+            let name = token_name(chain, token);
+            let action = Action::spend("batch_execute", chain, &name, amount.as_f64());
+            bundle.spends.push(authorize(action.to(to_address))?);
+            bundle
+                .requests
+                .push(transfer_erc20_request(token, to_address, amount.raw));
+            bundle
+                .changes
+                .push(BalanceChange::outgoing(token, amount.raw));
+        }
+        BatchAction::Approve {
+            token,
+            spender_address,
+            amount,
+        } => {
+            let token = erc20_param(chain, "token", &token).await?;
+            let spender_address = address_param("spender_address", &spender_address)?;
+            let amount = TokenAmount::parse(token, &amount, chain)
+                .await
+                .map_err(|e| tool_error("amount", e))?;
+            let name = token_name(chain, token);
+            bundle.spends.push(authorize(Action::approve(
+                "batch_execute",
+                chain,
+                &name,
+                amount.as_f64(),
+                spender_address,
+            ))?);
+            bundle
+                .requests
+                .push(approve_erc20_request(token, spender_address, amount.raw));
+        }
+        BatchAction::WrapEth { amount } => {
+            let weth = weth_address(chain).map_err(|e| tool_error("weth", e))?;
+            let amount = TokenAmount::parse(Address::ZERO, &amount, chain)
+                .await
+                .map_err(|e| tool_error("amount", e))?;
+            bundle.spends.push(authorize(Action::wrap(
+                "batch_execute",
+                chain,
+                "ETH",
+                amount.as_f64(),
+            ))?);
+            bundle.requests.push(deposit_weth_request(weth, amount.raw));
+            bundle.changes.extend([
+                BalanceChange::outgoing(Address::ZERO, amount.raw),
+                BalanceChange::incoming(weth, amount.raw),
+            ]);
+        }
+        BatchAction::UnwrapWeth { amount } => {
+            let weth = weth_address(chain).map_err(|e| tool_error("weth", e))?;
+            let amount = TokenAmount::parse(weth, &amount, chain)
+                .await
+                .map_err(|e| tool_error("amount", e))?;
+            bundle.spends.push(authorize(Action::wrap(
+                "batch_execute",
+                chain,
+                &token_name(chain, weth),
+                amount.as_f64(),
+            ))?);
+            bundle
+                .requests
+                .push(withdraw_weth_request(weth, amount.raw));
+            bundle.changes.extend([
+                BalanceChange::outgoing(weth, amount.raw),
+                BalanceChange::incoming(Address::ZERO, amount.raw),
+            ]);
+        }
+        BatchAction::Swap {
+            input_token,
+            output_token,
+            amount,
+            fee_on_transfer,
+            slippage_bps,
+        } => {
+            let input_token = token_param(chain, "input_token", &input_token).await?;
+            let output_token = token_param(chain, "output_token", &output_token).await?;
+            let amount = TokenAmount::parse(input_token, &amount, chain)
+                .await
+                .map_err(|e| tool_error("amount", e))?;
+            let params =
+                SwapParams::new(slippage_bps, None).map_err(|e| tool_error("swap_params", e))?;
+            let name = token_name(chain, input_token);
+            bundle.spends.push(authorize(Action::spend(
+                "batch_execute",
+                chain,
+                &name,
+                amount.as_f64(),
+            ))?);
+
+            let (router, request, expected_amount) = swap_v2_request(
+                provider,
+                chain,
+                input_token,
+                output_token,
+                amount.raw,
+                fee_on_transfer,
+                params,
+                owner,
+            )
+            .await
+            .map_err(|e| tool_error("swap", e))?;
+            if input_token != Address::ZERO {
+                bundle
+                    .requests
+                    .push(approve_erc20_request(input_token, router, amount.raw));
+            }
+            bundle.requests.push(request);
+            bundle.changes.extend([
+                BalanceChange::outgoing(input_token, amount.raw),
+                BalanceChange::incoming(output_token, expected_amount),
+            ]);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_actions() {
+    let actions: Vec<BatchAction> = serde_json::from_str(
+        r#"[
+            {"action": "approve", "token": "USDC", "spender_address": "0x0000000000000000000000000000000000000001", "amount": "10"},
+            {"action": "swap", "input_token": "USDC", "output_token": "ETH", "amount": "10"}
+        ]"#,
+    )
+    .unwrap();
+    assert!(matches!(actions[0], BatchAction::Approve { .. }));
+    assert!(matches!(
+        actions[1],
+        BatchAction::Swap {
+            fee_on_transfer: false,
+            slippage_bps: None,
+            ..
+        }
+    ));
+
+    let unknown = serde_json::from_str::<Vec<BatchAction>>(r#"[{"action": "bridge"}]"#);
+    assert!(unknown.is_err());
+}
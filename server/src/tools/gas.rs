@@ -0,0 +1,48 @@
+use super::tool_error;
+use crate::contracts::{amount::TokenAmount, common::make_provider};
+use crate::wallets::gas::{strategy_for, suggest_fees, GasStrategy};
+use alloy::primitives::{Address, U256};
+use anyhow::Result;
+use rig_derive::rig_tool;
+
+/// Gas used by a plain ETH transfer, used to express fees as a cost.
+const TRANSFER_GAS: u64 = 21_000;
+
+fn gwei(wei: u128) -> String {
+    format!("{:.4}", wei as f64 / 1e9)
+}
+
+#[rig_tool(
+    description = "Show current EIP-1559 fee levels (slow, normal, fast) on a chain, in gwei, with the cost of a plain ETH transfer at each and the strategy transactions are sent with",
+    params(chain = "The chain name, such as arbitrum")
+)]
+pub async fn gas_fees(chain: String) -> Result<String, rig::tool::ToolError> {
+    log::info!("chain_name: {}", chain);
+
+    let provider = make_provider(chain.clone()).map_err(|e| tool_error("provider", e))?;
+    let strategy = strategy_for(&chain).map_err(|e| tool_error("gas_strategy", e))?;
+
+    let mut levels = Vec::new();
+    for level in [GasStrategy::Slow, GasStrategy::Normal, GasStrategy::Fast] {
+        let fees = suggest_fees(&provider, &chain, level)
+            .await
+            .map_err(|e| tool_error("gas_fees", e))?;
+        let price =
+            (fees.base_fee_per_gas + fees.max_priority_fee_per_gas).min(fees.max_fee_per_gas);
+        let cost = U256::from(TRANSFER_GAS) * U256::from(price);
+        levels.push(serde_json::json!({
+            "strategy": level,
+            "base_fee_gwei": gwei(fees.base_fee_per_gas),
+            "max_fee_gwei": gwei(fees.max_fee_per_gas),
+            "max_priority_fee_gwei": gwei(fees.max_priority_fee_per_gas),
+            "transfer_cost_eth": TokenAmount::new(Address::ZERO, cost, 18).to_string(),
+        }));
+    }
+
+    Ok(serde_json::json!({
+        "chain": chain,
+        "strategy": strategy,
+        "levels": levels,
+    })
+    .to_string())
+}
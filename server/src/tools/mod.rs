@@ -1,3 +1,106 @@
+pub mod batch;
+pub mod gas;
 pub mod swap;
+pub mod tokens;
 pub mod transfer;
-pub mod assets;
\ No newline at end of file
+pub mod weth;
+pub mod assets;
+
+use crate::contracts::common::QuoteFailure;
+use crate::policy::{self, Action};
+use crate::service::token::registry;
+use crate::wallets::{
+    accounts, approvals, safe, simulate::SimulationFailure, tracker::Unconfirmed, Execution,
+};
+
+/// Run the policy engine for a tool action, returning the spend id to release on failure.
+pub(crate) fn authorize(action: Action) -> Result<Option<u64>, rig::tool::ToolError> {
+    policy::authorize(&action)
+        .map_err(|e| rig::tool::ToolError::ToolCallError(e.to_string().into()))
+}
+
+/// Settle the policy spends `authorize` recorded for a call of `tool` with the outcome of
+/// its transaction.
+///
+/// Spends of a broadcast transaction stay counted, even when its receipt did not arrive
+/// in time. A transaction of `tool` that was parked or proposed to the Safe takes them
+/// along and releases them if it is rejected. Otherwise nothing left the account and
+/// they are released, including when only a prerequisite (e.g. an approve) got parked.
+pub(crate) fn settle(
+    tool: &str,
+    spends: Vec<Option<u64>>,
+    result: &Result<Execution, anyhow::Error>,
+) {
+    let spends: Vec<u64> = spends.into_iter().flatten().collect();
+    let held = match result {
+        Ok(Execution::PendingApproval(action)) if action.ctx.tool == tool => {
+            approvals::hold_spends(action.id, &spends)
+        }
+        Ok(Execution::Proposed(proposal)) if proposal.ctx.tool == tool => {
+            safe::hold_spends(proposal.id, &spends)
+        }
+        Ok(Execution::PendingApproval(_) | Execution::Proposed(_)) => {
+            policy::release_all(&spends);
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+        Err(e) if e.is::<Unconfirmed>() => Ok(()),
+        Err(_) => {
+            policy::release_all(&spends);
+            Ok(())
+        }
+    };
+    if let Err(e) = held {
+        log::error!("policy: holding spends {:?} of {} failed: {}", spends, tool, e);
+    }
+}
+
+/// Turn a contract helper error into a tool error.
+///
+/// Simulation reverts and quote failures are returned as structured JSON so the agent can
+/// reason about them.
+pub(crate) fn tool_error(name: &str, e: anyhow::Error) -> rig::tool::ToolError {
+    if let Some(failure) = e.downcast_ref::<SimulationFailure>() {
+        return rig::tool::ToolError::ToolCallError(failure.to_json().into());
+    }
+    match e.downcast_ref::<QuoteFailure>() {
+        Some(failure) => rig::tool::ToolError::ToolCallError(failure.to_json().into()),
+        None => rig::tool::ToolError::ToolCallError(format!("{} error: {}", name, e).into()),
+    }
+}
+
+/// Resolve the optional `account` param of a tool against the session's account.
+pub(crate) fn account_param(
+    account: Option<String>,
+) -> Result<Option<String>, rig::tool::ToolError> {
+    let account = accounts::select(account)
+        .map_err(|e| rig::tool::ToolError::ToolCallError(e.to_string().into()))?;
+    accounts::account(account.as_deref()).map_err(|e| tool_error("account", e))?;
+    Ok(account)
+}
+
+/// Parse a token param given either as a symbol (e.g. `USDC`) or as a contract address.
+pub(crate) async fn token_param(
+    chain: &str,
+    param: &str,
+    value: &str,
+) -> Result<alloy::primitives::Address, rig::tool::ToolError> {
+    registry::resolve_address(chain, value).await.map_err(|e| {
+        rig::tool::ToolError::ToolCallError(format!("Invalid {}: {}", param, e).into())
+    })
+}
+
+/// Like `token_param`, for params that must be an ERC20 token rather than native ETH.
+pub(crate) async fn erc20_param(
+    chain: &str,
+    param: &str,
+    value: &str,
+) -> Result<alloy::primitives::Address, rig::tool::ToolError> {
+    let token = token_param(chain, param, value).await?;
+    if token.is_zero() {
+        return Err(rig::tool::ToolError::ToolCallError(
+            format!("Invalid {}: ETH is not an ERC20 token, use the ETH tools", param).into(),
+        ));
+    }
+    Ok(token)
+}
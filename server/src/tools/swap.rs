@@ -1,18 +1,27 @@
-use crate::contracts::{erc20::*, uniswap::swap};
+use super::{account_param, authorize, erc20_param, settle, token_param, tool_error};
+use crate::contracts::{
+    amount::{token_decimals, TokenAmount},
+    common::{make_provider, SwapParams},
+    erc20::*,
+    rpc,
+    swap::swap_v2,
+    uniswap::{self, executed_amounts, swap, NativeEth},
+};
+use crate::policy::{self, token_name, Action};
+use crate::wallets::{accounts, Execution};
 use alloy::primitives::Address;
 use anyhow::Result;
 use rig_derive::rig_tool;
 use std::str::FromStr;
 
-const MAX_SWAP_AMOUNT: u128 = 10u128.pow(5);
-
 #[rig_tool(
     description = "Approve an allowance for a spender",
     params(
         chain = "The chain name, such as arbitrum",
-        token_address = "The address of the ERC20 token contract",
+        token_address = "Symbol (e.g. USDC) or contract address of the ERC20 token",
         spender_address = "The address of the spender",
-        amount = "The amount of tokens to approve"
+        amount = "The amount of tokens to approve, in whole tokens",
+        account = "Optional named account to act for; defaults to the session's account"
     )
 )]
 pub async fn approve(
@@ -20,28 +29,37 @@ pub async fn approve(
     token_address: String,
     spender_address: String,
     amount: String,
+    account: Option<String>,
 ) -> Result<String, rig::tool::ToolError> {
-    let token_address = Address::from_str(&token_address)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid token_address format".into()))?;
+    let token_address = erc20_param(&chain, "token_address", &token_address).await?;
 
     let spender_address = Address::from_str(&spender_address).map_err(|_| {
         rig::tool::ToolError::ToolCallError("Invalid spender_address format".into())
     })?;
 
-    let amount = u128::from_str(&amount)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid amount format".into()))?;
+    let amount = TokenAmount::parse(token_address, &amount, &chain)
+        .await
+        .map_err(|e| tool_error("amount", e))?;
 
     println!(
         "chain_name: {}, token_address: {}, spender_address: {}, amount: {}",
         chain, token_address, spender_address, amount
     );
 
-    let result = approve_erc20(spender_address, amount, token_address, chain).await;
+    let account = account_param(account)?;
+    let token = token_name(&chain, token_address);
+    authorize(Action::approve(
+        "approve",
+        &chain,
+        &token,
+        amount.as_f64(),
+        spender_address,
+    ))?;
+
+    let result = approve_erc20(spender_address, amount.raw, token_address, chain, account).await;
     match result {
         Ok(h) => Ok(h.to_string()),
-        Err(e) => Err(rig::tool::ToolError::ToolCallError(
-            format!("approve_erc20 error: {}", e).into(),
-        )),
+        Err(e) => Err(tool_error("approve_erc20", e)),
     }
 }
 
@@ -49,7 +67,7 @@ pub async fn approve(
     description = "Check the current allowance for a spender",
     params(
         chain = "The chain name, such as arbitrum",
-        token_address = "The address of the ERC20 token contract",
+        token_address = "Symbol (e.g. USDC) or contract address of the ERC20 token",
         owner_address = "The address of the token owner",
         spender_address = "The address of the spender"
     )
@@ -60,8 +78,7 @@ pub async fn check_allowance(
     owner_address: String,
     spender_address: String,
 ) -> Result<String, rig::tool::ToolError> {
-    let token_address = Address::from_str(&token_address)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid token_address format".into()))?;
+    let token_address = erc20_param(&chain, "token_address", &token_address).await?;
 
     let owner_address = Address::from_str(&owner_address)
         .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid owner_address format".into()))?;
@@ -75,9 +92,13 @@ pub async fn check_allowance(
         chain, token_address, owner_address, spender_address
     );
 
-    let result = check_allowance_erc20(owner_address, spender_address, token_address, chain).await;
+    let result =
+        check_allowance_erc20(owner_address, spender_address, token_address, chain.clone()).await;
     match result {
-        Ok(allowance) => Ok(allowance.to_string()),
+        Ok(allowance) => TokenAmount::from_raw(token_address, allowance, &chain)
+            .await
+            .map(|amount| amount.to_string())
+            .map_err(|e| tool_error("decimals", e)),
         Err(e) => Err(rig::tool::ToolError::ToolCallError(
             format!("check_allowance_erc20 error: {}", e).into(),
         )),
@@ -85,12 +106,15 @@ pub async fn check_allowance(
 }
 
 #[rig_tool(
-    description = "Swap ERC20 tokens on Uniswap",
+    description = "Swap ERC20 tokens on Uniswap V3 and return the executed input and output amounts",
     params(
-        input_token = "Contract address of the input token",
-        output_token = "Contract address of the output token",
-        input_amount = "Amount of input token",
-        chain = "Chain name, e.g. arbitrum"
+        input_token = "Symbol (e.g. ETH, WETH) or contract address of the input token",
+        output_token = "Symbol (e.g. USDC, ETH) or contract address of the output token",
+        input_amount = "Amount of input token, in whole tokens (e.g. 0.5)",
+        chain = "Chain name, e.g. arbitrum",
+        slippage_bps = "Optional slippage tolerance in basis points (e.g. 50 for 0.5%)",
+        deadline_secs = "Optional number of seconds the swap stays valid",
+        account = "Optional named account to act for; defaults to the session's account"
     )
 )]
 pub async fn uniswap_v3_swap(
@@ -98,33 +122,325 @@ pub async fn uniswap_v3_swap(
     output_token: String,
     input_amount: String,
     chain: String,
+    slippage_bps: Option<u64>,
+    deadline_secs: Option<u64>,
+    account: Option<String>,
 ) -> Result<String, rig::tool::ToolError> {
-    let input_token = Address::from_str(&input_token).map_err(|_| {
-        rig::tool::ToolError::ToolCallError("Invalid input_token address format".into())
-    })?;
+    let input_token = token_param(&chain, "input_token", &input_token).await?;
 
-    let output_token = Address::from_str(&output_token).map_err(|_| {
-        rig::tool::ToolError::ToolCallError("Invalid output_token address format".into())
-    })?;
+    let output_token = token_param(&chain, "output_token", &output_token).await?;
 
-    let input_amount = u128::from_str(&input_amount)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid input_amount format".into()))?;
+    let input_amount = TokenAmount::parse(input_token, &input_amount, &chain)
+        .await
+        .map_err(|e| tool_error("input_amount", e))?;
+    let params =
+        SwapParams::new(slippage_bps, deadline_secs).map_err(|e| tool_error("swap_params", e))?;
+    let account = account_param(account)?;
+    let provider = make_provider(chain.clone()).map_err(|e| tool_error("provider", e))?;
+    let recipient = accounts::account(account.as_deref())
+        .map_err(|e| tool_error("account", e))?
+        .sender(&provider)
+        .await
+        .map_err(|e| tool_error("account", e))?;
 
-    if input_amount > MAX_SWAP_AMOUNT {
-        return Err(rig::tool::ToolError::ToolCallError(
-            format!(
-                "input_amount={} exceeds the maximum safe value={}",
-                input_amount, MAX_SWAP_AMOUNT
-            )
-            .into(),
-        ));
-    }
+    let token = token_name(&chain, input_token);
+    let spend = authorize(Action::spend(
+        "uniswap_v3_swap",
+        &chain,
+        &token,
+        input_amount.as_f64(),
+    ))?;
 
-    let swap_result = swap(input_token, output_token, input_amount, chain).await;
+    let native = NativeEth::of(input_token, output_token);
+    let route =
+        match uniswap::quote_best_route(input_token, output_token, input_amount.raw, chain.clone())
+            .await
+        {
+            Ok(best) => best.route,
+            Err(e) => {
+                policy::release(spend);
+                return Err(tool_error("quote_best_route", e));
+            }
+        };
+    // The allowance, quote and nonce of the swap are read from one endpoint.
+    let swap_result = rpc::pinned(swap(
+        route.clone(),
+        native,
+        input_amount.raw,
+        params,
+        chain.clone(),
+        account,
+    ))
+    .await;
+    settle("uniswap_v3_swap", vec![spend], &swap_result);
     match swap_result {
-        Ok(tx_hash) => Ok(tx_hash.to_string()),
-        Err(e) => Err(rig::tool::ToolError::ToolCallError(
-            format!("swap error: {}", e).into(),
-        )),
+        Ok(Execution::Sent(receipt, fees)) => {
+            let executed = executed_amounts(&receipt, recipient, &route, native);
+            let output_decimals = token_decimals(output_token, &chain)
+                .await
+                .map_err(|e| tool_error("decimals", e))?;
+            let amount_in =
+                TokenAmount::new(input_token, executed.amount_in, input_amount.decimals);
+            let amount_out = TokenAmount::new(output_token, executed.amount_out, output_decimals);
+            Ok(serde_json::json!({
+                "tx_hash": executed.tx_hash,
+                "input_token": input_token,
+                "output_token": output_token,
+                "amount_in": amount_in.to_string(),
+                "amount_out": amount_out.to_string(),
+                "fees": fees,
+            })
+            .to_string())
+        }
+        Ok(execution) => Ok(execution.to_string()),
+        Err(e) => Err(tool_error("swap", e)),
+    }
+}
+
+#[rig_tool(
+    description = "Find the Uniswap V3 route (fee tiers and intermediate tokens) with the best output for a swap",
+    params(
+        input_token = "Symbol (e.g. ETH, WETH) or contract address of the input token",
+        output_token = "Symbol (e.g. USDC, ETH) or contract address of the output token",
+        input_amount = "Amount of input token, in whole tokens (e.g. 0.5)",
+        chain = "Chain name, e.g. arbitrum"
+    )
+)]
+pub async fn quote_best_route(
+    input_token: String,
+    output_token: String,
+    input_amount: String,
+    chain: String,
+) -> Result<String, rig::tool::ToolError> {
+    let input_token = token_param(&chain, "input_token", &input_token).await?;
+
+    let output_token = token_param(&chain, "output_token", &output_token).await?;
+
+    let input_amount = TokenAmount::parse(input_token, &input_amount, &chain)
+        .await
+        .map_err(|e| tool_error("input_amount", e))?;
+
+    let best =
+        uniswap::quote_best_route(input_token, output_token, input_amount.raw, chain.clone())
+            .await
+            .map_err(|e| tool_error("quote_best_route", e))?;
+    let amount_out = TokenAmount::from_raw(output_token, best.amount_out, &chain)
+        .await
+        .map_err(|e| tool_error("decimals", e))?;
+    Ok(serde_json::json!({
+        "route": best.route,
+        "amount_in": input_amount.to_string(),
+        "amount_out": amount_out.to_string(),
+    })
+    .to_string())
+}
+
+#[rig_tool(
+    description = "Swap ETH for a specific ERC20 token",
+    params(
+        chain = "The chain name, such as arbitrum",
+        token_address = "Symbol (e.g. USDC) or contract address of the ERC20 token to receive",
+        amount = "The amount of ETH to swap, in ETH (e.g. 0.1)",
+        fee_on_transfer = "Whether the token takes a fee on transfer",
+        slippage_bps = "Optional slippage tolerance in basis points (e.g. 50 for 0.5%)",
+        deadline_secs = "Optional number of seconds the swap stays valid",
+        account = "Optional named account to act for; defaults to the session's account"
+    )
+)]
+pub async fn eth_swap_to_erc20(
+    chain: String,
+    token_address: String,
+    amount: String,
+    fee_on_transfer: bool,
+    slippage_bps: Option<u64>,
+    deadline_secs: Option<u64>,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let token_address = erc20_param(&chain, "token_address", &token_address).await?;
+
+    log::info!(
+        "chain_name: {}, token_address: {}, amount: {}",
+        chain,
+        token_address,
+        amount
+    );
+
+    swap_tool(
+        "eth_swap_to_erc20",
+        chain,
+        Address::ZERO,
+        token_address,
+        amount,
+        fee_on_transfer,
+        slippage_bps,
+        deadline_secs,
+        account,
+    )
+    .await
+}
+
+#[rig_tool(
+    description = "Sell a specific ERC20 token for ETH",
+    params(
+        chain = "The chain name, such as arbitrum",
+        token_address = "Symbol (e.g. USDC) or contract address of the ERC20 token to sell",
+        amount = "The amount of tokens to sell, in whole tokens (e.g. 100)",
+        fee_on_transfer = "Whether the token takes a fee on transfer",
+        slippage_bps = "Optional slippage tolerance in basis points (e.g. 50 for 0.5%)",
+        deadline_secs = "Optional number of seconds the swap stays valid",
+        account = "Optional named account to act for; defaults to the session's account"
+    )
+)]
+pub async fn erc20_swap_to_eth(
+    chain: String,
+    token_address: String,
+    amount: String,
+    fee_on_transfer: bool,
+    slippage_bps: Option<u64>,
+    deadline_secs: Option<u64>,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let token_address = erc20_param(&chain, "token_address", &token_address).await?;
+
+    log::info!(
+        "chain_name: {}, token_address: {}, amount: {}",
+        chain,
+        token_address,
+        amount
+    );
+
+    swap_tool(
+        "erc20_swap_to_eth",
+        chain,
+        token_address,
+        Address::ZERO,
+        amount,
+        fee_on_transfer,
+        slippage_bps,
+        deadline_secs,
+        account,
+    )
+    .await
+}
+
+#[rig_tool(
+    description = "Swap one ERC20 token for another through the chain's V2 router",
+    params(
+        chain = "The chain name, such as arbitrum",
+        input_token = "Symbol (e.g. LINK) or contract address of the token to sell",
+        output_token = "Symbol (e.g. USDC) or contract address of the token to receive",
+        amount = "The amount of input tokens to sell, in whole tokens",
+        fee_on_transfer = "Whether either token takes a fee on transfer",
+        slippage_bps = "Optional slippage tolerance in basis points (e.g. 50 for 0.5%)",
+        deadline_secs = "Optional number of seconds the swap stays valid",
+        account = "Optional named account to act for; defaults to the session's account"
+    )
+)]
+pub async fn erc20_swap_to_erc20(
+    chain: String,
+    input_token: String,
+    output_token: String,
+    amount: String,
+    fee_on_transfer: bool,
+    slippage_bps: Option<u64>,
+    deadline_secs: Option<u64>,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let input_token = erc20_param(&chain, "input_token", &input_token).await?;
+    let output_token = erc20_param(&chain, "output_token", &output_token).await?;
+
+    log::info!(
+        "chain_name: {}, input_token: {}, output_token: {}, amount: {}",
+        chain,
+        input_token,
+        output_token,
+        amount
+    );
+
+    swap_tool(
+        "erc20_swap_to_erc20",
+        chain,
+        input_token,
+        output_token,
+        amount,
+        fee_on_transfer,
+        slippage_bps,
+        deadline_secs,
+        account,
+    )
+    .await
+}
+
+// Shared body of the V2 swap tools: parse the amount, check policy, swap.
+async fn swap_tool(
+    tool: &str,
+    chain: String,
+    input_token: Address,
+    output_token: Address,
+    amount: String,
+    fee_on_transfer: bool,
+    slippage_bps: Option<u64>,
+    deadline_secs: Option<u64>,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let amount = TokenAmount::parse(input_token, &amount, &chain)
+        .await
+        .map_err(|e| tool_error("amount", e))?;
+    let params =
+        SwapParams::new(slippage_bps, deadline_secs).map_err(|e| tool_error("swap_params", e))?;
+    let account = account_param(account)?;
+
+    let token = token_name(&chain, input_token);
+    let spend = authorize(Action::spend(tool, &chain, &token, amount.as_f64()))?;
+
+    // The allowance, quote and nonce of the swap are read from one endpoint.
+    let result = rpc::pinned(swap_v2(
+        tool,
+        input_token,
+        output_token,
+        amount.raw,
+        fee_on_transfer,
+        params,
+        chain,
+        account,
+    ))
+    .await;
+    settle(tool, vec![spend], &result);
+
+    match result {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(tool_error(tool, e)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::chains::CHAIN_INFOS;
+    use rig::completion::Prompt;
+    use rig::providers::openai;
+
+    #[tokio::test]
+    async fn test_run() -> Result<()> {
+        let openai_client = openai::Client::from_url("sk-xxxxx", "https://api.xxxxx.xx/");
+
+        // Define the agent with the swap tool.
+        let swap_agent = openai_client
+            .agent("Qwen/Qwen2.5-32B-Instruct")
+            .preamble(
+                "You are a swap agent here to help the user perform ETH to ERC20 token swaps.",
+            )
+            .context(&serde_json::to_string(&*CHAIN_INFOS).unwrap())
+            .max_tokens(2048)
+            .tool(EthSwapToErc20)
+            .build();
+
+        // Prompt the agent and print the response.
+        println!("Swap ETH to ERC20 token");
+        println!(
+            "Swap Agent: {}",
+            swap_agent.prompt("Swap 0.1 ETH to USDC on base").await?
+        );
+        Ok(())
     }
 }
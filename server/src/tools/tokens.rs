@@ -0,0 +1,35 @@
+use super::tool_error;
+use crate::service::token::registry;
+use anyhow::Result;
+use rig_derive::rig_tool;
+
+#[rig_tool(
+    description = "Resolve a token symbol or contract address to its address, symbol, name and decimals",
+    params(
+        chain = "The chain name, such as arbitrum",
+        token = "Token symbol (e.g. USDC) or contract address"
+    )
+)]
+pub async fn resolve_token(chain: String, token: String) -> Result<String, rig::tool::ToolError> {
+    log::info!("chain_name: {}, token: {}", chain, token);
+
+    let info = registry::resolve(&chain, &token)
+        .await
+        .map_err(|e| tool_error("resolve_token", e))?;
+    serde_json::to_string(&info)
+        .map_err(|e| rig::tool::ToolError::ToolCallError(e.to_string().into()))
+}
+
+#[rig_tool(
+    description = "List the tokens known on a chain with their addresses and decimals",
+    params(chain = "The chain name, such as arbitrum")
+)]
+pub async fn list_tokens(chain: String) -> Result<String, rig::tool::ToolError> {
+    log::info!("chain_name: {}", chain);
+
+    let tokens = registry::list(&chain)
+        .await
+        .map_err(|e| tool_error("list_tokens", e))?;
+    serde_json::to_string(&tokens)
+        .map_err(|e| rig::tool::ToolError::ToolCallError(e.to_string().into()))
+}
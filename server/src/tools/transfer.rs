@@ -1,57 +1,47 @@
-use crate::contracts::{erc20::*, eth::transfer_eth};
+use super::{account_param, authorize, erc20_param, settle, tool_error};
+use crate::contracts::{amount::TokenAmount, erc20::*, eth::transfer_eth};
+use crate::policy::{token_name, Action};
 use alloy::primitives::Address;
 use anyhow::Result;
 use rig_derive::rig_tool;
 use std::str::FromStr;
 
-const MAX_AMOUNT: u128 = 10u128.pow(5);
-
-const MAX_ETH_AMOUNT: u128 = 10u128; //maximum amount in ETH
-
 #[rig_tool(
     description = "Transfer ETH to a specific address",
     params(
         chain = "The chain name, such as arbitrum",
         to_address = "The receiving address",
-        amount = "The amount of ETH to transfer"
+        amount = "The amount of ETH to transfer, in ETH (e.g. 0.25)",
+        account = "Optional named account to act for; defaults to the session's account"
     )
 )]
 pub async fn eth_transfer(
     chain: String,
     to_address: String,
     amount: String,
+    account: Option<String>,
 ) -> Result<String, rig::tool::ToolError> {
     let to_address = Address::from_str(&to_address)
         .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid to_address format".into()))?;
 
-    let amount = u128::from_str(&amount)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid amount format".into()))?;
+    let amount = TokenAmount::parse(Address::ZERO, &amount, &chain)
+        .await
+        .map_err(|e| tool_error("amount", e))?;
 
     println!(
         "chain_name: {}, to_address: {}, amount: {}",
         chain, to_address, amount
     );
 
-    if amount > MAX_ETH_AMOUNT {
-        println!(
-            "amount = {} exceeds the safe value = {}",
-            amount, MAX_ETH_AMOUNT
-        );
-        return Err(rig::tool::ToolError::ToolCallError(
-            format!(
-                "amount = {} exceeds the safe value = {}",
-                amount, MAX_ETH_AMOUNT
-            )
-            .into(),
-        ));
-    }
+    let account = account_param(account)?;
+    let action = Action::spend("eth_transfer", &chain, "ETH", amount.as_f64()).to(to_address);
+    let spend = authorize(action)?;
 
-    let result = transfer_eth(to_address, amount, chain).await;
+    let result = transfer_eth(to_address, amount.raw, chain, account).await;
+    settle("eth_transfer", vec![spend], &result);
     match result {
         Ok(h) => Ok(h.to_string()),
-        Err(e) => Err(rig::tool::ToolError::ToolCallError(
-            format!("transfer_eth error: {}", e).into(),
-        )),
+        Err(e) => Err(tool_error("transfer_eth", e)),
     }
 }
 
@@ -59,9 +49,10 @@ pub async fn eth_transfer(
     description = "Transfer ERC20 tokens to a specific address",
     params(
         chain = "The chain name, such as arbitrum",
-        token_address = "The address of the ERC20 token contract",
+        token_address = "Symbol (e.g. USDC) or contract address of the ERC20 token",
         to_address = "The receiving address",
-        amount = "The amount of tokens to transfer"
+        amount = "The amount of tokens to transfer, in whole tokens (e.g. 10 for 10 USDC)",
+        account = "Optional named account to act for; defaults to the session's account"
     )
 )]
 pub async fn erc20_transfer(
@@ -69,45 +60,35 @@ pub async fn erc20_transfer(
     token_address: String,
     to_address: String,
     amount: String,
+    account: Option<String>,
 ) -> Result<String, rig::tool::ToolError> {
-    let token_address = Address::from_str(&token_address)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid token_address format".into()))?;
+    let token_address = erc20_param(&chain, "token_address", &token_address).await?;
 
     let to_address = Address::from_str(&to_address)
         .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid to_address format".into()))?;
 
-    let amount = u128::from_str(&amount)
-        .map_err(|_| rig::tool::ToolError::ToolCallError("Invalid amount format".into()))?;
+    let amount = TokenAmount::parse(token_address, &amount, &chain)
+        .await
+        .map_err(|e| tool_error("amount", e))?;
 
     println!(
         "chain_name: {}, token_address: {}, to_address: {}, amount: {}",
         chain, token_address, to_address, amount
     );
 
-    if amount > MAX_AMOUNT {
-        println!(
-            "amount = {} exceeds the safe value = {}",
-            amount, MAX_AMOUNT
-        );
-        return Err(rig::tool::ToolError::ToolCallError(
-            format!(
-                "amount = {} exceeds the safe value = {}",
-                amount, MAX_AMOUNT
-            )
-            .into(),
-        ));
-    }
+    let account = account_param(account)?;
+    let token = token_name(&chain, token_address);
+    let action = Action::spend("erc20_transfer", &chain, &token, amount.as_f64()).to(to_address);
+    let spend = authorize(action)?;
 
-    let result = transfer_erc20(to_address, amount, token_address, chain).await;
+    let result = transfer_erc20(to_address, amount.raw, token_address, chain, account).await;
+    settle("erc20_transfer", vec![spend], &result);
     match result {
         Ok(h) => Ok(h.to_string()),
-        Err(e) => Err(rig::tool::ToolError::ToolCallError(
-            format!("transfer_erc20 error: {}", e).into(),
-        )),
+        Err(e) => Err(tool_error("transfer_erc20", e)),
     }
 }
 
-
 #[cfg(test)]
 mod test {
     use crate::chains::CHAIN_INFOS;
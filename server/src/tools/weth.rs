@@ -0,0 +1,72 @@
+use super::{account_param, authorize, tool_error};
+use crate::contracts::{
+    amount::TokenAmount,
+    weth::{deposit_weth, weth_address, withdraw_weth},
+};
+use crate::policy::{token_name, Action};
+use alloy::primitives::Address;
+use anyhow::Result;
+use rig_derive::rig_tool;
+
+#[rig_tool(
+    description = "Wrap native ETH into WETH",
+    params(
+        chain = "The chain name, such as arbitrum",
+        amount = "The amount of ETH to wrap, in ETH (e.g. 0.5)",
+        account = "Optional named account to act for; defaults to the session's account"
+    )
+)]
+pub async fn wrap_eth(
+    chain: String,
+    amount: String,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let amount = TokenAmount::parse(Address::ZERO, &amount, &chain)
+        .await
+        .map_err(|e| tool_error("amount", e))?;
+
+    log::info!("chain_name: {}, amount: {}", chain, amount);
+
+    // Wrapping keeps the value in the account, so no spend is recorded.
+    let account = account_param(account)?;
+    authorize(Action::wrap("wrap_eth", &chain, "ETH", amount.as_f64()))?;
+    let result = deposit_weth(amount.raw, chain, account).await;
+    match result {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(tool_error("deposit_weth", e)),
+    }
+}
+
+#[rig_tool(
+    description = "Unwrap WETH back into native ETH",
+    params(
+        chain = "The chain name, such as arbitrum",
+        amount = "The amount of WETH to unwrap, in WETH (e.g. 0.5)",
+        account = "Optional named account to act for; defaults to the session's account"
+    )
+)]
+pub async fn unwrap_weth(
+    chain: String,
+    amount: String,
+    account: Option<String>,
+) -> Result<String, rig::tool::ToolError> {
+    let weth = weth_address(&chain).map_err(|e| tool_error("weth", e))?;
+    let amount = TokenAmount::parse(weth, &amount, &chain)
+        .await
+        .map_err(|e| tool_error("amount", e))?;
+
+    log::info!("chain_name: {}, amount: {}", chain, amount);
+
+    let account = account_param(account)?;
+    authorize(Action::wrap(
+        "unwrap_weth",
+        &chain,
+        &token_name(&chain, weth),
+        amount.as_f64(),
+    ))?;
+    let result = withdraw_weth(amount.raw, chain, account).await;
+    match result {
+        Ok(h) => Ok(h.to_string()),
+        Err(e) => Err(tool_error("withdraw_weth", e)),
+    }
+}
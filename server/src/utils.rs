@@ -17,6 +17,10 @@ pub static OPENROUTER_API: Lazy<String> =
 pub static OPENROUTER_API_KEY: Lazy<String> =
     Lazy::new(|| env::var("OPENROUTER_API_KEY").unwrap_or_default());
 
+/// Shared handle to the local sled database; sled allows only one open handle per process.
+pub static DB: Lazy<sled::Db> =
+    Lazy::new(|| sled::open("agent_trade_db").expect("Failed to open agent_trade_db"));
+
 /// Global static collection of LP wallet addresses
 pub static LP_WALLETS: Lazy<Vec<String>> = Lazy::new(|| read_lp_wallets_config("config.json"));
 /// Configuration structure for LP wallets
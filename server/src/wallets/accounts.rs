@@ -0,0 +1,265 @@
+use std::{collections::HashMap, fs, future::Future, sync::RwLock};
+
+use alloy::{
+    primitives::Address,
+    providers::RootProvider,
+    signers::{
+        local::{coins_bip39::English, MnemonicBuilder, PrivateKeySigner},
+        Signer,
+    },
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    erc4337,
+    safe::SAFE_ADDRESS,
+    signer::{signer, AgentSigner, RemoteSigner},
+    ACCONT_TYPE,
+};
+
+/// Name of the account backed by the process signer and `ACCONT_TYPE`.
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Where a named account's key comes from. Secrets are referenced by env var name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SignerConfig {
+    Env {
+        private_key_env: String,
+    },
+    Mnemonic {
+        phrase_env: String,
+        derivation_path: String,
+    },
+    Remote {
+        url: String,
+        address: Address,
+        #[serde(default)]
+        token_env: Option<String>,
+    },
+}
+
+/// An entry of `configs/accounts.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountConfig {
+    pub name: String,
+    /// `LOCAL`, `EIP7702` or `ERC4337`, as for `ACCONT_TYPE`.
+    pub account_type: String,
+    pub signer: SignerConfig,
+}
+
+/// Named accounts from `configs/accounts.json`; the file is optional.
+pub static ACCOUNT_CONFIGS: Lazy<Vec<AccountConfig>> =
+    Lazy::new(|| match fs::read_to_string("configs/accounts.json") {
+        Ok(content) => serde_json::from_str(&content).expect("Failed to parse accounts.json"),
+        Err(_) => Vec::new(),
+    });
+
+/// Signers of named accounts, loaded on first use.
+static SIGNERS: Lazy<RwLock<HashMap<String, AgentSigner>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+tokio::task_local! {
+    /// Account the current API session acts for.
+    static SESSION_ACCOUNT: Option<String>;
+}
+
+/// A signing identity and the way it sends transactions.
+#[derive(Debug, Clone)]
+pub struct Account {
+    pub name: String,
+    pub account_type: String,
+    pub signer: AgentSigner,
+}
+
+impl Account {
+    /// Address of the signing key; for ERC-4337 accounts, the owner of the smart account.
+    pub fn address(&self) -> Address {
+        Signer::address(&self.signer)
+    }
+
+    /// Address the account's transactions act from: the Safe when one is configured, the
+    /// counterfactual smart account for ERC-4337 accounts, the signer otherwise. Use it for
+    /// balances, allowances and recipients.
+    pub async fn sender(&self, provider: &RootProvider) -> Result<Address, anyhow::Error> {
+        if let Some(safe) = *SAFE_ADDRESS {
+            return Ok(safe);
+        }
+        if self.account_type == "ERC4337" {
+            return erc4337::account_address(provider, self.address()).await;
+        }
+        Ok(self.address())
+    }
+}
+
+/// What the API shows about an account.
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountInfo {
+    pub name: String,
+    pub address: Address,
+    pub account_type: String,
+}
+
+fn env_secret(var: &str) -> Result<String, anyhow::Error> {
+    std::env::var(var).map_err(|_| anyhow!("Can not read env of {}", var))
+}
+
+fn load_signer(config: &SignerConfig) -> Result<AgentSigner, anyhow::Error> {
+    match config {
+        SignerConfig::Env { private_key_env } => {
+            let signer: PrivateKeySigner = env_secret(private_key_env)?
+                .parse()
+                .map_err(|e| anyhow!("parse {} error: {}", private_key_env, e))?;
+            Ok(AgentSigner::Local(signer))
+        }
+        SignerConfig::Mnemonic {
+            phrase_env,
+            derivation_path,
+        } => {
+            let signer = MnemonicBuilder::<English>::default()
+                .phrase(env_secret(phrase_env)?)
+                .derivation_path(derivation_path)?
+                .build()
+                .map_err(|e| anyhow!("derive mnemonic signer error: {}", e))?;
+            Ok(AgentSigner::Local(signer))
+        }
+        SignerConfig::Remote {
+            url,
+            address,
+            token_env,
+        } => {
+            let token = token_env.as_deref().map(env_secret).transpose()?;
+            Ok(AgentSigner::Remote(RemoteSigner::new(
+                url.clone(),
+                *address,
+                token,
+            )))
+        }
+    }
+}
+
+/// Resolve an account by name; `None` is the default account.
+pub fn account(name: Option<&str>) -> Result<Account, anyhow::Error> {
+    let name = name.unwrap_or(DEFAULT_ACCOUNT);
+    if name == DEFAULT_ACCOUNT {
+        return Ok(Account {
+            name: DEFAULT_ACCOUNT.to_string(),
+            account_type: ACCONT_TYPE.clone(),
+            signer: signer()?,
+        });
+    }
+
+    let config = ACCOUNT_CONFIGS
+        .iter()
+        .find(|config| config.name == name)
+        .ok_or_else(|| anyhow!("unknown account: {}", name))?;
+    if let Some(signer) = SIGNERS.read().unwrap().get(name) {
+        return Ok(Account {
+            name: config.name.clone(),
+            account_type: config.account_type.clone(),
+            signer: signer.clone(),
+        });
+    }
+
+    let signer = load_signer(&config.signer)?;
+    SIGNERS
+        .write()
+        .unwrap()
+        .insert(config.name.clone(), signer.clone());
+    Ok(Account {
+        name: config.name.clone(),
+        account_type: config.account_type.clone(),
+        signer,
+    })
+}
+
+/// Every account whose signer can be loaded, the default one first.
+pub fn list() -> Vec<AccountInfo> {
+    std::iter::once(DEFAULT_ACCOUNT)
+        .chain(ACCOUNT_CONFIGS.iter().map(|config| config.name.as_str()))
+        .filter_map(|name| match account(Some(name)) {
+            Ok(account) => Some(AccountInfo {
+                address: account.address(),
+                name: account.name,
+                account_type: account.account_type,
+            }),
+            Err(e) => {
+                log::warn!("account {} unavailable: {}", name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Drop the signers of named accounts. Local keys are zeroized when dropped.
+pub fn clear() {
+    SIGNERS.write().unwrap().clear();
+}
+
+/// Run `f` for an API session acting for `account`, `DEFAULT_ACCOUNT` if none.
+pub async fn with_session<F: Future>(account: Option<String>, f: F) -> F::Output {
+    let account = account.unwrap_or_else(|| DEFAULT_ACCOUNT.to_string());
+    SESSION_ACCOUNT.scope(Some(account), f).await
+}
+
+/// The account a tool acts for: the one it asked for, else the session's.
+///
+/// A session bound to an account cannot act for another one.
+pub fn select(requested: Option<String>) -> Result<Option<String>, anyhow::Error> {
+    let session = SESSION_ACCOUNT.try_with(|a| a.clone()).ok().flatten();
+    match (requested, session) {
+        (Some(requested), Some(session)) if requested != session => Err(anyhow!(
+            "this session acts for account {}, not {}",
+            session,
+            requested
+        )),
+        (requested, session) => Ok(requested.or(session)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_account_configs() {
+        let configs: Vec<AccountConfig> = serde_json::from_str(
+            r#"[
+                {"name": "momentum", "account_type": "LOCAL", "signer": {"type": "env", "private_key_env": "MOMENTUM_KEY"}},
+                {"name": "alice", "account_type": "EIP7702", "signer": {"type": "remote", "url": "http://signer", "address": "0x0000000000000000000000000000000000000001"}}
+            ]"#,
+        )
+        .unwrap();
+        assert!(matches!(configs[0].signer, SignerConfig::Env { .. }));
+        assert!(matches!(
+            configs[1].signer,
+            SignerConfig::Remote {
+                token_env: None,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_session_account() {
+        assert_eq!(select(None).unwrap(), None);
+
+        with_session(Some("alice".to_string()), async {
+            assert_eq!(select(None).unwrap(), Some("alice".to_string()));
+            assert!(select(Some("alice".to_string())).is_ok());
+            assert!(select(Some("bob".to_string())).is_err());
+        })
+        .await;
+
+        with_session(None, async {
+            assert_eq!(select(None).unwrap(), Some(DEFAULT_ACCOUNT.to_string()));
+            assert!(select(Some(DEFAULT_ACCOUNT.to_string())).is_ok());
+            assert!(select(Some("alice".to_string())).is_err());
+        })
+        .await;
+
+        assert!(account(Some("nobody")).is_err());
+    }
+}
@@ -0,0 +1,232 @@
+use alloy::{
+    primitives::TxHash,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use super::{execution::BalanceChange, gas::FeeEstimate, send_tx, tracker::Unconfirmed, TxContext};
+use crate::{
+    contracts::{amount::TokenAmount, common::make_provider, rpc},
+    policy::{self, token_name, POLICY},
+    utils::DB,
+};
+
+/// sled tree holding parked transactions, keyed by approval id.
+const APPROVAL_TREE: &str = "approvals";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Failed,
+}
+
+/// A transaction built by a tool and parked until a user approves or rejects it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingAction {
+    pub id: u64,
+    pub status: ApprovalStatus,
+    pub ctx: TxContext,
+    pub request: TransactionRequest,
+    pub changes: Vec<BalanceChange>,
+    pub created_at: i64,
+    pub decided_at: Option<i64>,
+    pub tx_hash: Option<TxHash>,
+    pub reason: Option<String>,
+    /// Policy spends of the tool call, released if the action is never sent.
+    #[serde(default)]
+    pub spends: Vec<u64>,
+    /// What sending it was expected to cost when it was parked.
+    #[serde(default)]
+    pub fees: Option<FeeEstimate>,
+}
+
+fn save(action: &PendingAction) -> Result<(), anyhow::Error> {
+    let tree = DB.open_tree(APPROVAL_TREE)?;
+    tree.insert(
+        action.id.to_be_bytes().to_vec(),
+        serde_json::to_vec(action)?,
+    )?;
+    Ok(())
+}
+
+/// Load a parked action by id.
+pub fn get(id: u64) -> Result<Option<PendingAction>, anyhow::Error> {
+    let tree = DB.open_tree(APPROVAL_TREE)?;
+    match tree.get(id.to_be_bytes().to_vec())? {
+        Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+        None => Ok(None),
+    }
+}
+
+/// Actions waiting for a decision, oldest first.
+pub fn list_pending() -> Result<Vec<PendingAction>, anyhow::Error> {
+    let tree = DB.open_tree(APPROVAL_TREE)?;
+    Ok(tree
+        .iter()
+        .values()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| serde_json::from_slice::<PendingAction>(&v).ok())
+        .filter(|a| a.status == ApprovalStatus::Pending)
+        .collect())
+}
+
+/// Whether the action needs a human decision before it is signed.
+///
+/// Tools listed in `approval.always` are always parked. Otherwise any outgoing
+/// amount above its token threshold, or above the USD threshold where a price is
+/// known, requires approval.
+pub async fn requires_approval(
+    ctx: &TxContext,
+    changes: &[BalanceChange],
+) -> Result<bool, anyhow::Error> {
+    let config = &POLICY.approval;
+    if !config.enabled {
+        return Ok(false);
+    }
+    if config.always.contains(&ctx.tool) {
+        return Ok(true);
+    }
+
+    for change in changes.iter().filter(|c| c.delta.is_negative()) {
+        let token = token_name(&ctx.chain, change.token);
+        let amount = TokenAmount::from_raw(change.token, change.delta.unsigned_abs(), &ctx.chain)
+            .await?
+            .as_f64();
+
+        if let Some(threshold) = config.token_thresholds.get(&token) {
+            if amount > *threshold {
+                return Ok(true);
+            }
+        }
+        if let (Some(price), Some(threshold)) =
+            (POLICY.usd_prices.get(&token), config.usd_threshold)
+        {
+            if amount * price > threshold {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Park a built transaction and return the record holding its approval id.
+pub fn park(
+    request: TransactionRequest,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+    fees: Option<FeeEstimate>,
+) -> Result<PendingAction, anyhow::Error> {
+    let action = PendingAction {
+        id: DB.generate_id()?,
+        status: ApprovalStatus::Pending,
+        ctx,
+        request,
+        changes,
+        created_at: chrono::Utc::now().timestamp(),
+        decided_at: None,
+        tx_hash: None,
+        reason: None,
+        spends: Vec::new(),
+        fees,
+    };
+    save(&action)?;
+    log::info!(
+        "parked {} on {} for approval as #{}",
+        action.ctx.tool,
+        action.ctx.chain,
+        action.id
+    );
+    Ok(action)
+}
+
+fn decide(
+    id: u64,
+    status: ApprovalStatus,
+    reason: Option<String>,
+) -> Result<PendingAction, anyhow::Error> {
+    let tree = DB.open_tree(APPROVAL_TREE)?;
+    let key = id.to_be_bytes().to_vec();
+    let current = tree
+        .get(&key)?
+        .ok_or_else(|| anyhow!("unknown approval #{}", id))?;
+
+    let mut action: PendingAction = serde_json::from_slice(&current)?;
+    if action.status != ApprovalStatus::Pending {
+        return Err(anyhow!("approval #{} is already {:?}", id, action.status));
+    }
+    action.status = status;
+    action.reason = reason;
+    action.decided_at = Some(chrono::Utc::now().timestamp());
+
+    // Compare-and-swap so two concurrent decisions cannot both win.
+    tree.compare_and_swap(&key, Some(current), Some(serde_json::to_vec(&action)?))?
+        .map_err(|_| anyhow!("approval #{} was decided concurrently", id))?;
+    Ok(action)
+}
+
+/// Hand the policy spends of the tool call that parked action `id` over to it.
+pub fn hold_spends(id: u64, spends: &[u64]) -> Result<(), anyhow::Error> {
+    if spends.is_empty() {
+        return Ok(());
+    }
+    let tree = DB.open_tree(APPROVAL_TREE)?;
+    let key = id.to_be_bytes().to_vec();
+    let current = tree
+        .get(&key)?
+        .ok_or_else(|| anyhow!("unknown approval #{}", id))?;
+    let mut action: PendingAction = serde_json::from_slice(&current)?;
+    action.spends.extend_from_slice(spends);
+    tree.compare_and_swap(&key, Some(current), Some(serde_json::to_vec(&action)?))?
+        .map_err(|_| anyhow!("approval #{} was decided concurrently", id))?;
+    // Rejected in the meantime: the spends were never sent.
+    if action.status == ApprovalStatus::Rejected {
+        policy::release_all(spends);
+    }
+    Ok(())
+}
+
+/// Approve a parked action and sign it through `send_tx`.
+pub async fn approve(id: u64) -> Result<TransactionReceipt, anyhow::Error> {
+    let mut action = decide(id, ApprovalStatus::Approved, None)?;
+    log::info!("approval #{} granted for {}", id, action.ctx.tool);
+
+    let provider = make_provider(action.ctx.chain.clone())?;
+    let sent = rpc::pinned(send_tx(
+        action.request.clone(),
+        provider,
+        action.ctx.clone(),
+    ))
+    .await;
+    match sent {
+        Ok(receipt) => {
+            action.tx_hash = Some(receipt.transaction_hash);
+            save(&action)?;
+            Ok(receipt)
+        }
+        Err(e) => {
+            match e.downcast_ref::<Unconfirmed>() {
+                // Broadcast, so the spends stay counted.
+                Some(unconfirmed) => action.tx_hash = Some(unconfirmed.hash),
+                None => {
+                    action.status = ApprovalStatus::Failed;
+                    policy::release_all(&action.spends);
+                }
+            }
+            action.reason = Some(e.to_string());
+            save(&action)?;
+            Err(e)
+        }
+    }
+}
+
+/// Reject a parked action; it will never be signed.
+pub fn reject(id: u64, reason: Option<String>) -> Result<PendingAction, anyhow::Error> {
+    let action = decide(id, ApprovalStatus::Rejected, reason)?;
+    policy::release_all(&action.spends);
+    log::info!("approval #{} rejected for {}", id, action.ctx.tool);
+    Ok(action)
+}
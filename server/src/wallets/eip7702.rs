@@ -1,68 +1,289 @@
-use std::sync::Arc;
+use std::env;
 
 use alloy::{
-    consensus::SignableTransaction,
-    dyn_abi::JsonAbiExt,
-    json_abi::Function,
-    network::{EthereumWallet, TransactionBuilder},
-    primitives::{keccak256, TxKind},
-    providers::{Provider, ProviderBuilder, RootProvider, WalletProvider},
-    rpc::types::{TransactionReceipt, TransactionRequest},
-    signers::{local::PrivateKeySigner, Signature, Signer},
+    eips::eip7702::{Authorization, SignedAuthorization},
+    network::{TransactionBuilder, TransactionBuilder7702},
+    primitives::{Address, TxKind, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::TransactionRequest,
+    signers::Signer,
+    sol_types::SolCall,
 };
 use anyhow::anyhow;
+use once_cell::sync::Lazy;
 
-// eip7702 tx
-pub async fn send_7702_tx(
-    request: TransactionRequest,
-    provider: RootProvider,
-) -> Result<TransactionReceipt, anyhow::Error> {
-    // Read the private key from the environment variable
-    // let private_key = env::var("PRIVATE_KEY").unwrap();
-    let request_to_build = request.clone();
-
-    // [RISK WARNING! Writing a private key in the code file is insecure behavior.]
-    // The following code is for testing only. Set up signer from private key, be aware of danger.
-    let private_key = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
-    let priv_signer: PrivateKeySigner = private_key.parse().expect("parse PrivateKeySigner");
-    let wallet: EthereumWallet = EthereumWallet::from(priv_signer.clone());
-
-    let signer = Arc::new(
-        ProviderBuilder::new()
-            .wallet(wallet)
-            .on_provider(provider.clone()),
-    );
-
-    let account = signer.default_signer_address();
-
-    //  Access eoa's delegated code
-    let input = Function::parse("nonce()")?.abi_encode_input(&[])?;
-    let aa_req: TransactionRequest = TransactionRequest::default()
-        .input(input.into())
-        .to(account);
-    let output = provider.call(aa_req).await?;
-
-    let mut preimage = vec![0u8];
-    preimage.extend_from_slice(output.as_ref());
-    let to_address = match request.to.unwrap() {
-        TxKind::Create => return Err(anyhow!("Need request.to param")),
-        TxKind::Call(to) => to,
+use super::{
+    accounts, execute,
+    execution::{ExecutionMode, EXECUTION_MODE},
+    safe::SAFE_ADDRESS,
+    Account, AgentSigner, BalanceChange, Execution, TxContext,
+};
+use crate::{
+    contracts::abi::ISmartAccount,
+    policy::{self, Action},
+};
+
+/// Prefix of the code EIP-7702 installs on a delegated account, followed by the delegate.
+const DELEGATION_PREFIX: [u8; 3] = [0xef, 0x01, 0x00];
+
+/// Smart-account implementation the EOA delegates to, from `EIP7702_IMPLEMENTATION`.
+pub static DELEGATE_IMPLEMENTATION: Lazy<Option<Address>> = Lazy::new(|| {
+    env::var("EIP7702_IMPLEMENTATION").ok().map(|v| {
+        v.parse()
+            .expect("Cannot parse EIP7702_IMPLEMENTATION env var")
+    })
+});
+
+/// Delegate of an account given its code, or `None` if it is not delegated.
+pub fn parse_delegation(code: &[u8]) -> Option<Address> {
+    if code.len() == 23 && code.starts_with(&DELEGATION_PREFIX) {
+        Some(Address::from_slice(&code[3..]))
+    } else {
+        None
+    }
+}
+
+/// Current delegate of `account`, read with `eth_getCode`.
+pub async fn current_delegation(
+    provider: &RootProvider,
+    account: Address,
+) -> Result<Option<Address>, anyhow::Error> {
+    let code = provider.get_code_at(account).await?;
+    Ok(parse_delegation(&code))
+}
+
+/// Sign an authorization letting the account of `signer` run the code of `delegate`.
+///
+/// `Address::ZERO` as delegate clears the delegation.
+pub async fn sign_authorization(
+    signer: &AgentSigner,
+    chain_id: u64,
+    delegate: Address,
+    nonce: u64,
+) -> Result<SignedAuthorization, anyhow::Error> {
+    let authorization = Authorization {
+        chain_id: U256::from(chain_id),
+        address: delegate,
+        nonce,
+    };
+    let signature = signer.sign_hash(&authorization.signature_hash()).await?;
+    Ok(authorization.into_signed(signature))
+}
+
+/// Send a type-4 transaction from `account` to itself that sets its delegate, through
+/// `execute` so the execution mode, policy and approvals apply to it.
+///
+/// The policy sees the delegate as the recipient of an approval, since it gets full
+/// control of the account. The authorization is left unsigned here: the account sends
+/// the transaction itself, so the authorization has to carry the nonce after the one
+/// `send_tx` reserves, and is signed there.
+pub async fn set_delegation(
+    provider: &RootProvider,
+    chain_name: &str,
+    account: &Account,
+    delegate: Address,
+) -> Result<Execution, anyhow::Error> {
+    if account.account_type == "ERC4337" {
+        return Err(anyhow!(
+            "{} is a smart account and can't delegate",
+            account.name
+        ));
+    }
+    let tool = if delegate == Address::ZERO {
+        "eip7702:revoke"
+    } else {
+        "eip7702:delegate"
+    };
+    let mut action = Action::approve(tool, chain_name, "ETH", 0.0, delegate);
+    if delegate == Address::ZERO {
+        action.recipient = None;
+    }
+    policy::authorize(&action)?;
+
+    let address = account.address();
+    let chain_id = provider.get_chain_id().await?;
+    let authorization = Authorization {
+        chain_id: U256::from(chain_id),
+        address: delegate,
+        nonce: 0,
     };
-    preimage.extend_from_slice(to_address.as_ref());
-    let data = request.input.data.unwrap();
-    preimage.extend_from_slice(data.as_ref());
-    let value = request.value;
-    preimage.extend_from_slice(&value.unwrap().to_be_bytes_vec());
-
-    let digest = keccak256(&preimage);
-    let sig: Signature = priv_signer.sign_hash(&digest).await?;
-
-    let tx = request_to_build.build_unsigned().unwrap();
-    let signed_tx = tx.eip1559().unwrap().clone().into_signed(sig);
-    let pending_tx = signer.send_tx_envelope(signed_tx.into()).await?;
-
-    pending_tx
-        .get_receipt()
-        .await
-        .map_err(|e| anyhow!(e.to_string()))
+    let request = TransactionRequest::default()
+        .with_to(address)
+        .with_authorization_list(vec![SignedAuthorization::new_unchecked(
+            authorization,
+            0,
+            U256::ZERO,
+            U256::ZERO,
+        )]);
+    let ctx = TxContext::new(chain_name, tool).with_account(Some(account.name.clone()));
+    execute(request, provider.clone(), ctx, vec![]).await
+}
+
+/// Delegate `account` to `DELEGATE_IMPLEMENTATION`.
+pub async fn delegate(
+    provider: &RootProvider,
+    chain_name: &str,
+    account: &Account,
+) -> Result<Execution, anyhow::Error> {
+    let implementation =
+        DELEGATE_IMPLEMENTATION.ok_or_else(|| anyhow!("EIP7702_IMPLEMENTATION is not set"))?;
+    set_delegation(provider, chain_name, account, implementation).await
+}
+
+/// Clear the delegation of `account`.
+pub async fn revoke(
+    provider: &RootProvider,
+    chain_name: &str,
+    account: &Account,
+) -> Result<Execution, anyhow::Error> {
+    set_delegation(provider, chain_name, account, Address::ZERO).await
+}
+
+/// Fold `requests` into one call of the account to itself, running the delegate's batch
+/// `execute` so that they all succeed or all revert.
+pub fn batch_call(
+    account: Address,
+    requests: Vec<TransactionRequest>,
+) -> Result<TransactionRequest, anyhow::Error> {
+    if requests.is_empty() {
+        return Err(anyhow!("batch has no calls"));
+    }
+    let calls = requests
+        .into_iter()
+        .map(|request| match request.to {
+            Some(TxKind::Call(to)) => Ok(ISmartAccount::Call {
+                to,
+                value: request.value.unwrap_or_default(),
+                data: request.input.input().cloned().unwrap_or_default(),
+            }),
+            _ => Err(anyhow!("batched calls can't create contracts")),
+        })
+        .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+    Ok(TransactionRequest::default()
+        .with_to(account)
+        .with_input(ISmartAccount::executeCall { calls }.abi_encode()))
+}
+
+/// Run `requests` atomically through the delegate of the account in `ctx`.
+///
+/// `changes` are the combined balance effects of all calls, as for `execute`.
+pub async fn execute_batch(
+    requests: Vec<TransactionRequest>,
+    provider: RootProvider,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+) -> Result<Execution, anyhow::Error> {
+    if SAFE_ADDRESS.is_some() {
+        return Err(anyhow!(
+            "batches run through the EIP-7702 delegate of the signer and can't be proposed to the Safe"
+        ));
+    }
+    let account = accounts::account(ctx.account.as_deref())?;
+    // A smart account would wrap the EOA's self-call in a user operation of its own.
+    if account.account_type == "ERC4337" {
+        return Err(anyhow!(
+            "{} is a smart account; batches run through the EIP-7702 delegate of an EOA",
+            account.name
+        ));
+    }
+    let account = account.address();
+    // Paper trades never touch the chain, so they don't need the delegate.
+    if *EXECUTION_MODE != ExecutionMode::Paper
+        && current_delegation(&provider, account).await?.is_none()
+    {
+        return Err(anyhow!(
+            "{} is not delegated, set up the delegation first via /eip7702/delegate",
+            account
+        ));
+    }
+    let request = batch_call(account, requests)?;
+    execute(request, provider, ctx, changes).await
+}
+
+/// Turn a call of the delegated `account` into a call of the account to itself running
+/// it through the delegate's `execute`, so that the account sends it as a plain
+/// transaction.
+pub async fn delegated_call(
+    provider: &RootProvider,
+    account: Address,
+    request: TransactionRequest,
+) -> Result<TransactionRequest, anyhow::Error> {
+    if current_delegation(provider, account).await?.is_none() {
+        return Err(anyhow!(
+            "{} is not delegated, set up the delegation first via /eip7702/delegate",
+            account
+        ));
+    }
+    let from = request.from;
+    let mut call = batch_call(account, vec![request])?;
+    call.from = from;
+    Ok(call)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::contracts::common::make_provider;
+
+    #[test]
+    fn test_batch_call() {
+        let account = Address::repeat_byte(0xaa);
+        let token = Address::repeat_byte(0x01);
+        let to = Address::repeat_byte(0x02);
+        let requests = vec![
+            TransactionRequest::default()
+                .with_to(token)
+                .with_input(vec![0x12, 0x34]),
+            TransactionRequest::default()
+                .with_to(to)
+                .with_value(U256::from(5u64)),
+        ];
+
+        let request = batch_call(account, requests).unwrap();
+        assert_eq!(request.to, Some(TxKind::Call(account)));
+        let calls = ISmartAccount::executeCall::abi_decode(request.input.input().unwrap(), true)
+            .unwrap()
+            .calls;
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].to, token);
+        assert_eq!(calls[0].data.as_ref(), &[0x12, 0x34]);
+        assert_eq!(calls[1].value, U256::from(5u64));
+
+        assert!(batch_call(account, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_parse_delegation() {
+        let delegate = Address::repeat_byte(0x42);
+        let mut code = DELEGATION_PREFIX.to_vec();
+        code.extend_from_slice(delegate.as_slice());
+        assert_eq!(parse_delegation(&code), Some(delegate));
+
+        assert_eq!(parse_delegation(&[]), None);
+        assert_eq!(parse_delegation(&code[..22]), None);
+        code[0] = 0x60;
+        assert_eq!(parse_delegation(&code), None);
+    }
+
+    // Needs `anvil --hardfork prague` and EIP7702_IMPLEMENTATION pointing at a deployed account.
+    #[tokio::test]
+    #[ignore]
+    async fn test_delegate_and_revoke() -> Result<(), anyhow::Error> {
+        let provider = make_provider(String::from("local"))?;
+        let account = accounts::account(None)?;
+
+        delegate(&provider, "local", &account).await?;
+        assert_eq!(
+            current_delegation(&provider, account.address()).await?,
+            *DELEGATE_IMPLEMENTATION
+        );
+
+        revoke(&provider, "local", &account).await?;
+        assert_eq!(
+            current_delegation(&provider, account.address()).await?,
+            None
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,457 @@
+use std::env;
+
+use alloy::{
+    primitives::{aliases::U192, keccak256, Address, Bytes, TxKind, B256, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::{Log, TransactionReceipt, TransactionRequest},
+    signers::Signer,
+    sol_types::{SolCall, SolValue},
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{
+    gas,
+    tracker::{Unconfirmed, TX_POLL_INTERVAL, TX_WAIT_TIMEOUT},
+    Account, AgentSigner, TxContext,
+};
+use crate::contracts::abi::{IEntryPoint, ISimpleAccount, ISimpleAccountFactory};
+use crate::utils;
+
+/// Canonical EntryPoint v0.7 deployment.
+const ENTRY_POINT_V07: &str = "0x0000000071727De22E5E9d8BAf0edAc6f37da032";
+
+/// Placeholder signature for gas estimation, shaped like a real ECDSA signature.
+const DUMMY_SIGNATURE: [u8; 65] = {
+    let mut signature = [0xff; 65];
+    signature[64] = 0x1c;
+    signature
+};
+
+pub static ENTRY_POINT: Lazy<Address> = Lazy::new(|| {
+    env::var("ERC4337_ENTRY_POINT")
+        .unwrap_or_else(|_| ENTRY_POINT_V07.to_string())
+        .parse()
+        .expect("Cannot parse ERC4337_ENTRY_POINT env var")
+});
+
+/// Salt the smart account was created with by the factory.
+pub static ACCOUNT_SALT: Lazy<U256> = Lazy::new(|| {
+    env::var("ERC4337_ACCOUNT_SALT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(U256::ZERO)
+});
+
+/// A v0.7 UserOperation in the unpacked form used by the bundler RPC.
+///
+/// Paymasters are not supported; the account pays for its own gas.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperation {
+    pub sender: Address,
+    pub nonce: U256,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory: Option<Address>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory_data: Option<Bytes>,
+    pub call_data: Bytes,
+    pub call_gas_limit: U256,
+    pub verification_gas_limit: U256,
+    pub pre_verification_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    pub signature: Bytes,
+}
+
+/// Two 128-bit values packed into one word, high first, as in `PackedUserOperation`.
+fn pack_u128(high: U256, low: U256) -> B256 {
+    B256::from((high << 128) | low)
+}
+
+impl UserOperation {
+    /// The hash the account owner signs: the packed operation bound to the
+    /// EntryPoint and chain.
+    pub fn hash(&self, entry_point: Address, chain_id: u64) -> B256 {
+        let mut init_code = Vec::new();
+        if let Some(factory) = self.factory {
+            init_code.extend_from_slice(factory.as_slice());
+            if let Some(factory_data) = &self.factory_data {
+                init_code.extend_from_slice(factory_data);
+            }
+        }
+        let packed = (
+            self.sender,
+            self.nonce,
+            keccak256(&init_code),
+            keccak256(&self.call_data),
+            pack_u128(self.verification_gas_limit, self.call_gas_limit),
+            self.pre_verification_gas,
+            pack_u128(self.max_priority_fee_per_gas, self.max_fee_per_gas),
+            keccak256(b""),
+        )
+            .abi_encode();
+        keccak256((keccak256(packed), entry_point, U256::from(chain_id)).abi_encode())
+    }
+}
+
+/// Gas limits returned by `eth_estimateUserOperationGas`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperationGas {
+    pub pre_verification_gas: U256,
+    pub verification_gas_limit: U256,
+    pub call_gas_limit: U256,
+}
+
+/// Result of `eth_getUserOperationReceipt`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserOperationReceipt {
+    pub user_op_hash: B256,
+    pub sender: Address,
+    pub success: bool,
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub actual_gas_used: U256,
+    pub actual_gas_cost: U256,
+    #[serde(default)]
+    pub logs: Vec<Log>,
+    /// The bundle transaction that included the operation.
+    pub receipt: TransactionReceipt,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<serde_json::Value>,
+}
+
+/// JSON-RPC client for an ERC-4337 bundler.
+#[derive(Debug, Clone)]
+pub struct Bundler {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Bundler {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// The bundler configured by `ERC4337_BUNDLER_URL`.
+    pub fn from_env() -> Self {
+        Self::new(utils::read_parse_env::<String>("ERC4337_BUNDLER_URL"))
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Option<T>, anyhow::Error> {
+        let body = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": method,
+            "params": params,
+        });
+        let response: RpcResponse<T> = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        match response.error {
+            Some(error) => Err(anyhow!("{} error: {}", method, error)),
+            None => Ok(response.result),
+        }
+    }
+
+    pub async fn estimate_gas(
+        &self,
+        user_op: &UserOperation,
+        entry_point: Address,
+    ) -> Result<UserOperationGas, anyhow::Error> {
+        self.request(
+            "eth_estimateUserOperationGas",
+            serde_json::json!([user_op, entry_point]),
+        )
+        .await?
+        .ok_or_else(|| anyhow!("eth_estimateUserOperationGas returned no result"))
+    }
+
+    pub async fn send(
+        &self,
+        user_op: &UserOperation,
+        entry_point: Address,
+    ) -> Result<B256, anyhow::Error> {
+        self.request(
+            "eth_sendUserOperation",
+            serde_json::json!([user_op, entry_point]),
+        )
+        .await?
+        .ok_or_else(|| anyhow!("eth_sendUserOperation returned no result"))
+    }
+
+    /// The receipt of `user_op_hash`, or `None` while it is not included.
+    pub async fn receipt(
+        &self,
+        user_op_hash: B256,
+    ) -> Result<Option<UserOperationReceipt>, anyhow::Error> {
+        self.request(
+            "eth_getUserOperationReceipt",
+            serde_json::json!([user_op_hash]),
+        )
+        .await
+    }
+
+    /// Poll until `user_op_hash` is included, up to `TX_WAIT_TIMEOUT`.
+    pub async fn wait_for_receipt(
+        &self,
+        user_op_hash: B256,
+    ) -> Result<UserOperationReceipt, anyhow::Error> {
+        let started = tokio::time::Instant::now();
+        loop {
+            if let Some(receipt) = self.receipt(user_op_hash).await? {
+                return Ok(receipt);
+            }
+            if started.elapsed() >= *TX_WAIT_TIMEOUT {
+                return Err(anyhow!(
+                    "user operation {} still pending after {}s",
+                    user_op_hash,
+                    TX_WAIT_TIMEOUT.as_secs()
+                ));
+            }
+            tokio::time::sleep(*TX_POLL_INTERVAL).await;
+        }
+    }
+}
+
+/// Address of the smart account of `owner`, which the factory deploys on first use.
+pub async fn account_address(
+    provider: &RootProvider,
+    owner: Address,
+) -> Result<Address, anyhow::Error> {
+    let factory = ISimpleAccountFactory::ISimpleAccountFactoryInstance::new(
+        utils::read_parse_env::<Address>("ERC4337_FACTORY"),
+        provider.clone(),
+    );
+    Ok(factory
+        .getAddress(owner, *ACCOUNT_SALT)
+        .call()
+        .await
+        .map_err(|e| anyhow!("factory getAddress error: {}", e))?
+        ._0)
+}
+
+/// Wrap `request` into an unsigned UserOperation calling it from the smart account
+/// of `owner`.
+pub async fn build_user_op(
+    request: &TransactionRequest,
+    provider: &RootProvider,
+    chain: &str,
+    owner: Address,
+) -> Result<UserOperation, anyhow::Error> {
+    let to = match request.to {
+        Some(TxKind::Call(to)) => to,
+        _ => return Err(anyhow!("user operations can't create contracts")),
+    };
+    let sender = account_address(provider, owner).await?;
+
+    // Deploy the account with the operation if it doesn't exist yet.
+    let (factory, factory_data) = if provider.get_code_at(sender).await?.is_empty() {
+        let call = ISimpleAccountFactory::createAccountCall {
+            owner,
+            salt: *ACCOUNT_SALT,
+        };
+        (
+            Some(utils::read_parse_env::<Address>("ERC4337_FACTORY")),
+            Some(call.abi_encode().into()),
+        )
+    } else {
+        (None, None)
+    };
+
+    let entry_point = IEntryPoint::IEntryPointInstance::new(*ENTRY_POINT, provider.clone());
+    let nonce = entry_point
+        .getNonce(sender, U192::ZERO)
+        .call()
+        .await
+        .map_err(|e| anyhow!("getNonce error: {}", e))?
+        .nonce;
+
+    let call_data = ISimpleAccount::executeCall {
+        dest: to,
+        value: request.value.unwrap_or_default(),
+        func: request.input.input().cloned().unwrap_or_default(),
+    };
+    let fees = gas::suggest_fees(provider, chain, gas::strategy_for(chain)?).await?;
+
+    Ok(UserOperation {
+        sender,
+        nonce,
+        factory,
+        factory_data,
+        call_data: call_data.abi_encode().into(),
+        max_fee_per_gas: U256::from(fees.max_fee_per_gas),
+        max_priority_fee_per_gas: U256::from(fees.max_priority_fee_per_gas),
+        ..Default::default()
+    })
+}
+
+/// Estimate, sign with the account owner and submit `user_op` through `bundler`,
+/// returning its hash.
+pub async fn submit_user_op(
+    mut user_op: UserOperation,
+    owner: &AgentSigner,
+    bundler: &Bundler,
+    chain_id: u64,
+) -> Result<B256, anyhow::Error> {
+    user_op.signature = Bytes::copy_from_slice(&DUMMY_SIGNATURE);
+    let gas = bundler.estimate_gas(&user_op, *ENTRY_POINT).await?;
+    user_op.pre_verification_gas = gas.pre_verification_gas;
+    user_op.verification_gas_limit = gas.verification_gas_limit;
+    user_op.call_gas_limit = gas.call_gas_limit;
+
+    // SimpleAccount checks an EIP-191 signature over the operation hash.
+    let hash = user_op.hash(*ENTRY_POINT, chain_id);
+    let signature = owner.sign_message(hash.as_slice()).await?;
+    user_op.signature = Bytes::copy_from_slice(&signature.as_bytes());
+
+    bundler.send(&user_op, *ENTRY_POINT).await
+}
+
+/// Send `request` from the smart account of `account` as a UserOperation and wait for
+/// its bundle.
+///
+/// The returned receipt is the one of the bundle transaction; a reverted operation is
+/// reported as an error because the bundle itself still succeeds.
+pub async fn send_user_op(
+    request: TransactionRequest,
+    provider: RootProvider,
+    ctx: TxContext,
+    account: &Account,
+) -> Result<TransactionReceipt, anyhow::Error> {
+    let chain_id = provider.get_chain_id().await?;
+    let bundler = Bundler::from_env();
+
+    let user_op = build_user_op(&request, &provider, &ctx.chain, account.address()).await?;
+    let user_op_hash = submit_user_op(user_op, &account.signer, &bundler, chain_id).await?;
+    log::info!(
+        "{} sent user operation {} on {}",
+        ctx.tool,
+        user_op_hash,
+        ctx.chain
+    );
+
+    let receipt = bundler
+        .wait_for_receipt(user_op_hash)
+        .await
+        .map_err(|e| Unconfirmed::new(user_op_hash, e))?;
+    if !receipt.success {
+        return Err(anyhow!(
+            "user operation {} reverted in {}: {}",
+            user_op_hash,
+            receipt.receipt.transaction_hash,
+            receipt.reason.unwrap_or_default()
+        ));
+    }
+    Ok(receipt.receipt)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy::signers::{local::PrivateKeySigner, Signature};
+    use axum::{routing::post, Json, Router};
+    use std::sync::{Arc, Mutex};
+
+    // Minimal bundler stand-in: fixed gas estimate, remembers the sent operation.
+    async fn start_bundler(sent: Arc<Mutex<Option<UserOperation>>>) -> String {
+        let app = Router::new().route(
+            "/",
+            post(move |Json(body): Json<serde_json::Value>| {
+                let sent = sent.clone();
+                async move {
+                    let result = match body["method"].as_str().unwrap() {
+                        "eth_estimateUserOperationGas" => serde_json::json!({
+                            "preVerificationGas": "0xb000",
+                            "verificationGasLimit": "0x20000",
+                            "callGasLimit": "0x10000",
+                        }),
+                        "eth_sendUserOperation" => {
+                            let user_op = serde_json::from_value(body["params"][0].clone());
+                            *sent.lock().unwrap() = Some(user_op.unwrap());
+                            serde_json::json!(B256::repeat_byte(0x11))
+                        }
+                        _ => serde_json::Value::Null,
+                    };
+                    Json(serde_json::json!({ "jsonrpc": "2.0", "id": 1, "result": result }))
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    #[tokio::test]
+    async fn test_submit_user_op() {
+        let key = PrivateKeySigner::random();
+        let owner = AgentSigner::Local(key.clone());
+
+        let sent = Arc::new(Mutex::new(None));
+        let bundler = Bundler::new(start_bundler(sent.clone()).await);
+        let user_op = UserOperation {
+            sender: Address::repeat_byte(0x01),
+            nonce: U256::from(3u64),
+            call_data: Bytes::from_static(&[0xb6, 0x1d, 0x27, 0xf6]),
+            max_fee_per_gas: U256::from(2_000_000_000u64),
+            max_priority_fee_per_gas: U256::from(1_000_000_000u64),
+            ..Default::default()
+        };
+
+        let hash = submit_user_op(user_op, &owner, &bundler, 31337)
+            .await
+            .unwrap();
+        assert_eq!(hash, B256::repeat_byte(0x11));
+        assert!(bundler.receipt(hash).await.unwrap().is_none());
+
+        let sent = sent.lock().unwrap().clone().unwrap();
+        assert_eq!(sent.call_gas_limit, U256::from(0x10000u64));
+        assert_eq!(sent.pre_verification_gas, U256::from(0xb000u64));
+        let signature = Signature::from_raw(&sent.signature).unwrap();
+        let recovered = signature
+            .recover_address_from_msg(sent.hash(*ENTRY_POINT, 31337))
+            .unwrap();
+        assert_eq!(recovered, key.address());
+    }
+
+    #[test]
+    fn test_user_op_hash_binds_gas_and_chain() {
+        let user_op = UserOperation {
+            sender: Address::repeat_byte(0x01),
+            call_gas_limit: U256::from(1u64),
+            ..Default::default()
+        };
+        let hash = user_op.hash(*ENTRY_POINT, 1);
+        assert_ne!(hash, user_op.hash(*ENTRY_POINT, 10));
+
+        let mut other = user_op.clone();
+        other.call_gas_limit = U256::from(2u64);
+        assert_ne!(hash, other.hash(*ENTRY_POINT, 1));
+
+        // The signature is not part of the hash.
+        other = user_op.clone();
+        other.signature = Bytes::from_static(&[1]);
+        assert_eq!(hash, other.hash(*ENTRY_POINT, 1));
+    }
+}
@@ -0,0 +1,271 @@
+use std::{env, fmt};
+
+use alloy::{
+    primitives::{Address, Bytes, TxKind, I256, U256},
+    providers::RootProvider,
+    rpc::types::{TransactionReceipt, TransactionRequest},
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    accounts,
+    approvals::{self, PendingAction},
+    gas::{self, FeeEstimate},
+    paper, safe, send_priced,
+    simulate::{simulate, simulate_bundle},
+    TxContext,
+};
+
+/// How on-chain tools execute the transactions they build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecutionMode {
+    /// Build and simulate only; return calldata and expected effects.
+    DryRun,
+    /// Apply expected effects to a simulated ledger in sled; never sign.
+    Paper,
+    /// Sign and broadcast through `send_tx`.
+    Live,
+}
+
+impl std::str::FromStr for ExecutionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dry_run" => Ok(ExecutionMode::DryRun),
+            "paper" => Ok(ExecutionMode::Paper),
+            "live" => Ok(ExecutionMode::Live),
+            other => Err(anyhow!("unknown execution mode: {}", other)),
+        }
+    }
+}
+
+pub static EXECUTION_MODE: Lazy<ExecutionMode> = Lazy::new(|| {
+    env::var("EXECUTION_MODE")
+        .map(|v| v.parse().expect("Cannot parse EXECUTION_MODE env var"))
+        .unwrap_or(ExecutionMode::Live)
+});
+
+/// Expected change of one balance of the acting account. `Address::ZERO` is the native token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceChange {
+    pub token: Address,
+    pub delta: I256,
+}
+
+impl BalanceChange {
+    pub fn incoming(token: Address, amount: U256) -> Self {
+        Self {
+            token,
+            delta: I256::from_raw(amount),
+        }
+    }
+
+    pub fn outgoing(token: Address, amount: U256) -> Self {
+        Self {
+            token,
+            delta: -I256::from_raw(amount),
+        }
+    }
+}
+
+/// What a dry run would have sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DryRunReport {
+    pub mode: ExecutionMode,
+    pub chain: String,
+    pub tool: String,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub calldata: Bytes,
+    pub gas: u64,
+    /// Fees and cost the transaction would be sent with.
+    pub fees: FeeEstimate,
+    pub expected_changes: Vec<BalanceChange>,
+}
+
+/// Result of running a tool transaction in the configured mode.
+#[derive(Debug)]
+pub enum Execution {
+    /// Sent and included, with the fees it was priced with.
+    Sent(TransactionReceipt, Option<FeeEstimate>),
+    DryRun(DryRunReport),
+    Paper(paper::PaperFill),
+    /// Parked until a user approves it via `/approvals/approve`.
+    PendingApproval(PendingAction),
+    /// Proposed to the Safe, executed once enough owners confirm it.
+    Proposed(safe::SafeProposal),
+}
+
+impl fmt::Display for Execution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Execution::Sent(receipt, fees) => write!(
+                f,
+                "{}",
+                serde_json::json!({
+                    "tx_hash": receipt.transaction_hash,
+                    "fees": fees,
+                })
+            ),
+            Execution::DryRun(report) => {
+                write!(
+                    f,
+                    "{}",
+                    serde_json::to_string(report).map_err(|_| fmt::Error)?
+                )
+            }
+            Execution::PendingApproval(action) => write!(
+                f,
+                "{}",
+                serde_json::json!({
+                    "status": "pending_approval",
+                    "approval_id": action.id,
+                    "tool": action.ctx.tool,
+                    "chain": action.ctx.chain,
+                    "fees": action.fees,
+                    "message": "The transaction was not sent. It waits for the user to approve it.",
+                })
+            ),
+            Execution::Proposed(proposal) => write!(
+                f,
+                "{}",
+                serde_json::json!({
+                    "status": "safe_proposal",
+                    "proposal_id": proposal.id,
+                    "proposal_status": proposal.status,
+                    "safe": proposal.safe,
+                    "safe_tx_hash": proposal.safe_tx_hash,
+                    "confirmations": proposal.confirmations.len(),
+                    "threshold": proposal.threshold,
+                    "tx_hash": proposal.tx_hash,
+                    "message": "The transaction was proposed to the Safe. It runs once enough owners confirm it.",
+                })
+            ),
+            Execution::Paper(fill) => {
+                write!(
+                    f,
+                    "{}",
+                    serde_json::to_string(fill).map_err(|_| fmt::Error)?
+                )
+            }
+        }
+    }
+}
+
+/// Like `execute`, for a transaction that needs `prior` ones which were not sent, such
+/// as a swap whose approve was skipped in dry-run mode.
+///
+/// Dry runs simulate `prior` and `request` together and report `request`; the other
+/// modes run `request` alone.
+pub async fn execute_after(
+    prior: Vec<TransactionRequest>,
+    request: TransactionRequest,
+    provider: RootProvider,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+) -> Result<Execution, anyhow::Error> {
+    match *EXECUTION_MODE {
+        ExecutionMode::DryRun => dry_run(prior, request, provider, ctx, changes).await,
+        _ => execute(request, provider, ctx, changes).await,
+    }
+}
+
+/// Simulate `request` after `prior` from the acting account and report what would be sent.
+async fn dry_run(
+    mut prior: Vec<TransactionRequest>,
+    mut request: TransactionRequest,
+    provider: RootProvider,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+) -> Result<Execution, anyhow::Error> {
+    let from = accounts::account(ctx.account.as_deref())?
+        .sender(&provider)
+        .await?;
+    request.from = Some(from);
+    let gas = if prior.is_empty() {
+        simulate(&provider, &request).await?
+    } else {
+        prior.iter_mut().for_each(|prior| prior.from = Some(from));
+        prior.push(request.clone());
+        let gas = simulate_bundle(&provider, &prior).await?;
+        gas.last().copied().unwrap_or_default()
+    };
+    request.gas = Some(gas);
+    let fees = gas::estimate(&provider, &ctx.chain, &request).await?;
+    let to = match request.to {
+        Some(TxKind::Call(to)) => Some(to),
+        _ => None,
+    };
+    Ok(Execution::DryRun(DryRunReport {
+        mode: ExecutionMode::DryRun,
+        chain: ctx.chain,
+        tool: ctx.tool,
+        from,
+        to,
+        value: request.value.unwrap_or_default(),
+        calldata: request.input.input().cloned().unwrap_or_default(),
+        gas,
+        fees,
+        expected_changes: changes,
+    }))
+}
+
+/// Fees `send_priced` would price `request` with if it was sent now. Operations of smart
+/// accounts are priced by the bundler and give `None`.
+async fn estimate_fees(
+    request: &TransactionRequest,
+    provider: &RootProvider,
+    ctx: &TxContext,
+) -> Result<Option<FeeEstimate>, anyhow::Error> {
+    let account = accounts::account(ctx.account.as_deref())?;
+    if account.account_type == "ERC4337" {
+        return Ok(None);
+    }
+    let mut request = request.clone();
+    request.from = Some(account.address());
+    request.gas = Some(simulate(provider, &request).await?);
+    Ok(Some(gas::estimate(provider, &ctx.chain, &request).await?))
+}
+
+/// Run `request` according to `EXECUTION_MODE`.
+///
+/// `changes` are the balance effects the calling tool expects, used for dry-run
+/// reports and for the paper ledger.
+pub async fn execute(
+    request: TransactionRequest,
+    provider: RootProvider,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+) -> Result<Execution, anyhow::Error> {
+    match *EXECUTION_MODE {
+        ExecutionMode::Live => {
+            // Treasury setups act from the Safe, so its owners decide on every transaction.
+            // Delegations are the exception: they are sent by the signer for itself.
+            if safe::SAFE_ADDRESS.is_some() && request.authorization_list.is_none() {
+                let proposal = safe::propose(request, &provider, ctx, changes).await?;
+                return Ok(Execution::Proposed(proposal));
+            }
+            if approvals::requires_approval(&ctx, &changes).await? {
+                // The user gets to see the cost before approving; an estimate that
+                // fails now doesn't keep the transaction from being parked.
+                let fees = estimate_fees(&request, &provider, &ctx)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::warn!("fee estimate of {} failed: {}", ctx.tool, e);
+                        None
+                    });
+                let action = approvals::park(request, ctx, changes, fees)?;
+                return Ok(Execution::PendingApproval(action));
+            }
+            let (receipt, fees) = send_priced(request, provider, ctx).await?;
+            Ok(Execution::Sent(receipt, fees))
+        }
+        ExecutionMode::DryRun => dry_run(Vec::new(), request, provider, ctx, changes).await,
+        ExecutionMode::Paper => Ok(Execution::Paper(paper::apply(&ctx, changes).await?)),
+    }
+}
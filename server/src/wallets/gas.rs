@@ -0,0 +1,356 @@
+use std::{env, fmt, str::FromStr};
+
+use alloy::{
+    consensus::{SignableTransaction, TxEip1559},
+    eips::BlockNumberOrTag,
+    primitives::{address, Address, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::TransactionRequest,
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::chains::{get_chain_info, GasPolicy};
+use crate::contracts::abi::IGasPriceOracle;
+
+/// Recent blocks the priority fee is sampled from.
+const FEE_HISTORY_BLOCKS: u64 = 10;
+
+/// OP-stack `GasPriceOracle` predeploy.
+const GAS_PRICE_ORACLE: Address = address!("420000000000000000000000000000000000000F");
+
+const WEI_PER_GWEI: f64 = 1e9;
+
+/// How aggressively to bid for inclusion.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum GasStrategy {
+    Slow,
+    Normal,
+    Fast,
+    /// Pay this percentile of the priority fees in recent blocks.
+    Percentile(f64),
+}
+
+impl GasStrategy {
+    pub fn percentile(&self) -> f64 {
+        match self {
+            GasStrategy::Slow => 10.0,
+            GasStrategy::Normal => 50.0,
+            GasStrategy::Fast => 90.0,
+            GasStrategy::Percentile(p) => *p,
+        }
+    }
+}
+
+impl FromStr for GasStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "slow" => Ok(GasStrategy::Slow),
+            "normal" => Ok(GasStrategy::Normal),
+            "fast" => Ok(GasStrategy::Fast),
+            other => {
+                let percentile = other
+                    .strip_prefix('p')
+                    .and_then(|p| p.parse::<f64>().ok())
+                    .filter(|p| (0.0..=100.0).contains(p))
+                    .ok_or_else(|| anyhow!("unknown gas strategy: {}", s))?;
+                Ok(GasStrategy::Percentile(percentile))
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for GasStrategy {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for GasStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GasStrategy::Slow => write!(f, "slow"),
+            GasStrategy::Normal => write!(f, "normal"),
+            GasStrategy::Fast => write!(f, "fast"),
+            GasStrategy::Percentile(p) => write!(f, "p{}", p),
+        }
+    }
+}
+
+impl From<GasStrategy> for String {
+    fn from(strategy: GasStrategy) -> Self {
+        strategy.to_string()
+    }
+}
+
+pub static GAS_STRATEGY: Lazy<GasStrategy> = Lazy::new(|| {
+    env::var("GAS_STRATEGY")
+        .map(|v| v.parse().expect("Cannot parse GAS_STRATEGY env var"))
+        .unwrap_or(GasStrategy::Normal)
+});
+
+fn gas_policy(chain: &str) -> GasPolicy {
+    get_chain_info(chain)
+        .map(|info| info.gas)
+        .unwrap_or_default()
+}
+
+/// Strategy for `chain`: its `chains.json` override, else `GAS_STRATEGY`.
+pub fn strategy_for(chain: &str) -> Result<GasStrategy, anyhow::Error> {
+    match gas_policy(chain).strategy {
+        Some(strategy) => strategy.parse(),
+        None => Ok(*GAS_STRATEGY),
+    }
+}
+
+fn gwei_to_wei(gwei: f64) -> u128 {
+    (gwei * WEI_PER_GWEI) as u128
+}
+
+/// EIP-1559 fee fields for the next block.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Fees {
+    pub base_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+/// Median of the per-block rewards at the requested percentile, ignoring empty blocks.
+fn median_reward(rewards: &[Vec<u128>]) -> Option<u128> {
+    let mut samples: Vec<u128> = rewards
+        .iter()
+        .filter_map(|block| block.first().copied())
+        .filter(|reward| *reward > 0)
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_unstable();
+    Some(samples[samples.len() / 2])
+}
+
+/// Clamp fees to the chain's caps, refusing if the base fee alone is above them.
+fn apply_caps(policy: &GasPolicy, chain: &str, fees: Fees) -> Result<Fees, anyhow::Error> {
+    let mut fees = fees;
+    if let Some(cap) = policy.max_priority_fee_gwei.map(gwei_to_wei) {
+        fees.max_priority_fee_per_gas = fees.max_priority_fee_per_gas.min(cap);
+    }
+    if let Some(cap) = policy.max_fee_gwei.map(gwei_to_wei) {
+        if fees.base_fee_per_gas > cap {
+            return Err(anyhow!(
+                "base fee of {:.2} gwei on {} is above the {} gwei cap",
+                fees.base_fee_per_gas as f64 / WEI_PER_GWEI,
+                chain,
+                policy.max_fee_gwei.unwrap_or_default()
+            ));
+        }
+        fees.max_fee_per_gas = fees.max_fee_per_gas.min(cap);
+    }
+    fees.max_priority_fee_per_gas = fees.max_priority_fee_per_gas.min(fees.max_fee_per_gas);
+    Ok(fees)
+}
+
+/// Refuse a max fee above the chain's cap, e.g. for bumped replacements.
+pub fn check_cap(chain: &str, max_fee_per_gas: u128) -> Result<(), anyhow::Error> {
+    match gas_policy(chain).max_fee_gwei {
+        Some(cap) if max_fee_per_gas > gwei_to_wei(cap) => Err(anyhow!(
+            "max fee of {:.2} gwei on {} is above the {} gwei cap",
+            max_fee_per_gas as f64 / WEI_PER_GWEI,
+            chain,
+            cap
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Fees for `strategy` from `eth_feeHistory`, capped by the chain's policy.
+///
+/// The max fee leaves room for the base fee to double before the transaction stalls.
+pub async fn suggest_fees(
+    provider: &RootProvider,
+    chain: &str,
+    strategy: GasStrategy,
+) -> Result<Fees, anyhow::Error> {
+    let history = provider
+        .get_fee_history(
+            FEE_HISTORY_BLOCKS,
+            BlockNumberOrTag::Latest,
+            &[strategy.percentile()],
+        )
+        .await?;
+    // The last entry is the base fee of the next block.
+    let base_fee_per_gas = history
+        .base_fee_per_gas
+        .last()
+        .copied()
+        .ok_or_else(|| anyhow!("eth_feeHistory returned no base fee on {}", chain))?;
+    let max_priority_fee_per_gas = match history.reward.as_deref().and_then(median_reward) {
+        Some(reward) => reward,
+        None => provider.get_max_priority_fee_per_gas().await?,
+    };
+
+    let fees = Fees {
+        base_fee_per_gas,
+        max_fee_per_gas: base_fee_per_gas * 2 + max_priority_fee_per_gas,
+        max_priority_fee_per_gas,
+    };
+    apply_caps(&gas_policy(chain), chain, fees)
+}
+
+/// L1 data fee an OP-stack chain charges for `request`, in wei.
+pub async fn l1_data_fee(
+    provider: &RootProvider,
+    request: &TransactionRequest,
+    chain_id: u64,
+) -> Result<U256, anyhow::Error> {
+    // The oracle prices the unsigned RLP encoding; it adds the signature overhead itself.
+    let tx = TxEip1559 {
+        chain_id,
+        nonce: request.nonce.unwrap_or_default(),
+        gas_limit: request.gas.unwrap_or_default(),
+        max_fee_per_gas: request.max_fee_per_gas.unwrap_or_default(),
+        max_priority_fee_per_gas: request.max_priority_fee_per_gas.unwrap_or_default(),
+        to: request.to.unwrap_or_default(),
+        value: request.value.unwrap_or_default(),
+        access_list: Default::default(),
+        input: request.input.input().cloned().unwrap_or_default(),
+    };
+    let oracle = IGasPriceOracle::IGasPriceOracleInstance::new(GAS_PRICE_ORACLE, provider.clone());
+    Ok(oracle
+        .getL1Fee(tx.encoded_for_signing().into())
+        .call()
+        .await
+        .map_err(|e| anyhow!("getL1Fee error: {}", e))?
+        ._0)
+}
+
+/// What a transaction is expected to cost, reported to the agent before it is sent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeEstimate {
+    pub strategy: GasStrategy,
+    pub gas_limit: u64,
+    #[serde(flatten)]
+    pub fees: Fees,
+    /// Charged on top of execution by OP-stack chains, in wei.
+    pub l1_data_fee: Option<U256>,
+    /// Cost at the current base fee, in wei.
+    pub estimated_cost: U256,
+    /// Cost if the base fee rises up to the max fee, in wei.
+    pub max_cost: U256,
+}
+
+impl FeeEstimate {
+    /// Set the fee fields on `request` so the provider's fillers keep them.
+    pub fn apply(&self, request: &mut TransactionRequest) {
+        request.gas = Some(self.gas_limit);
+        request.max_fee_per_gas = Some(self.fees.max_fee_per_gas);
+        request.max_priority_fee_per_gas = Some(self.fees.max_priority_fee_per_gas);
+    }
+}
+
+impl fmt::Display for FeeEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} fees, max fee {:.2} gwei, estimated cost {} wei (max {} wei)",
+            self.strategy,
+            self.fees.max_fee_per_gas as f64 / WEI_PER_GWEI,
+            self.estimated_cost,
+            self.max_cost
+        )
+    }
+}
+
+/// Price `request` on `chain` with the chain's strategy and caps.
+///
+/// Uses `request.gas` as the limit when it is set, e.g. after simulation.
+pub async fn estimate(
+    provider: &RootProvider,
+    chain: &str,
+    request: &TransactionRequest,
+) -> Result<FeeEstimate, anyhow::Error> {
+    let strategy = strategy_for(chain)?;
+    let fees = suggest_fees(provider, chain, strategy).await?;
+    let gas_limit = match request.gas {
+        Some(gas) => gas,
+        None => provider.estimate_gas(request.clone()).await?,
+    };
+
+    let l1_data_fee = if gas_policy(chain).op_stack {
+        let chain_id = provider.get_chain_id().await?;
+        let mut priced = request.clone();
+        priced.gas = Some(gas_limit);
+        priced.max_fee_per_gas = Some(fees.max_fee_per_gas);
+        priced.max_priority_fee_per_gas = Some(fees.max_priority_fee_per_gas);
+        Some(l1_data_fee(provider, &priced, chain_id).await?)
+    } else {
+        None
+    };
+
+    let gas = U256::from(gas_limit);
+    let l1 = l1_data_fee.unwrap_or_default();
+    let effective_price = fees.base_fee_per_gas + fees.max_priority_fee_per_gas;
+    Ok(FeeEstimate {
+        strategy,
+        gas_limit,
+        fees,
+        l1_data_fee,
+        estimated_cost: gas * U256::from(effective_price.min(fees.max_fee_per_gas)) + l1,
+        max_cost: gas * U256::from(fees.max_fee_per_gas) + l1,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_strategy() {
+        assert_eq!("fast".parse::<GasStrategy>().unwrap(), GasStrategy::Fast);
+        assert_eq!(
+            "p75".parse::<GasStrategy>().unwrap(),
+            GasStrategy::Percentile(75.0)
+        );
+        assert!("p150".parse::<GasStrategy>().is_err());
+        assert!("urgent".parse::<GasStrategy>().is_err());
+
+        let json = serde_json::to_string(&GasStrategy::Percentile(75.0)).unwrap();
+        assert_eq!(json, "\"p75\"");
+    }
+
+    #[test]
+    fn test_median_reward_skips_empty_blocks() {
+        let rewards = vec![vec![0], vec![3], vec![1], vec![2]];
+        assert_eq!(median_reward(&rewards), Some(2));
+        assert_eq!(median_reward(&[vec![0], vec![]]), None);
+    }
+
+    #[test]
+    fn test_apply_caps() {
+        let policy = GasPolicy {
+            max_fee_gwei: Some(10.0),
+            max_priority_fee_gwei: Some(1.0),
+            ..Default::default()
+        };
+        let fees = Fees {
+            base_fee_per_gas: gwei_to_wei(6.0),
+            max_fee_per_gas: gwei_to_wei(14.0),
+            max_priority_fee_per_gas: gwei_to_wei(2.0),
+        };
+        let capped = apply_caps(&policy, "test", fees).unwrap();
+        assert_eq!(capped.max_fee_per_gas, gwei_to_wei(10.0));
+        assert_eq!(capped.max_priority_fee_per_gas, gwei_to_wei(1.0));
+
+        let spike = Fees {
+            base_fee_per_gas: gwei_to_wei(12.0),
+            ..fees
+        };
+        assert!(apply_caps(&policy, "test", spike).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use std::{env, fs, path::Path};
+
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::anyhow;
+use serde::Deserialize;
+use zeroize::Zeroizing;
+
+use super::signer::{install_signer, AgentSigner, SIGNER_TYPE};
+use crate::utils;
+
+/// Minimal view of a Web3 Secret Storage (V3) file, used to validate it before decrypting.
+#[derive(Debug, Deserialize)]
+struct KeystoreHeader {
+    version: u8,
+    crypto: KeystoreCrypto,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeystoreCrypto {
+    kdf: String,
+}
+
+/// Decrypt a V3 JSON keystore protected by either scrypt or pbkdf2.
+pub fn load_keystore(path: &str, passphrase: &str) -> Result<PrivateKeySigner, anyhow::Error> {
+    let content =
+        fs::read_to_string(path).map_err(|e| anyhow!("read keystore {} error: {}", path, e))?;
+    let header: KeystoreHeader = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("parse keystore {} error: {}", path, e))?;
+
+    if header.version != 3 {
+        return Err(anyhow!("unsupported keystore version: {}", header.version));
+    }
+    match header.crypto.kdf.as_str() {
+        "scrypt" | "pbkdf2" => {}
+        other => return Err(anyhow!("unsupported keystore kdf: {}", other)),
+    }
+
+    PrivateKeySigner::decrypt_keystore(Path::new(path), passphrase)
+        .map_err(|e| anyhow!("decrypt keystore {} error: {}", path, e))
+}
+
+/// Read the keystore passphrase from `KEYSTORE_PASSWORD_FILE`, or prompt for it on the terminal.
+pub fn read_passphrase(path: &str) -> Result<Zeroizing<String>, anyhow::Error> {
+    if let Ok(password_file) = env::var("KEYSTORE_PASSWORD_FILE") {
+        let content = Zeroizing::new(
+            fs::read_to_string(&password_file)
+                .map_err(|e| anyhow!("read passphrase file {} error: {}", password_file, e))?,
+        );
+        return Ok(Zeroizing::new(
+            content.trim_end_matches(['\r', '\n']).to_string(),
+        ));
+    }
+
+    let passphrase = rpassword::prompt_password(format!("Passphrase for keystore {}: ", path))
+        .map_err(|e| anyhow!("read passphrase error: {}", e))?;
+    Ok(Zeroizing::new(passphrase))
+}
+
+/// Unlock the configured keystore once at startup.
+///
+/// Does nothing unless `SIGNER_TYPE=KEYSTORE`. The decrypted key only lives in the
+/// in-memory signer slot and is wiped by `signer::clear_signer` on shutdown.
+pub fn unlock_at_startup() -> Result<(), anyhow::Error> {
+    if SIGNER_TYPE.as_str() != "KEYSTORE" {
+        return Ok(());
+    }
+
+    let path = utils::read_parse_env::<String>("KEYSTORE_PATH");
+    let passphrase = read_passphrase(&path)?;
+    let signer = load_keystore(&path, &passphrase)?;
+    log::info!("Unlocked keystore for account {}", signer.address());
+
+    install_signer(AgentSigner::Local(signer));
+    Ok(())
+}
@@ -1,23 +1,20 @@
 use std::sync::Arc;
 
 use alloy::{
-    network::EthereumWallet,
+    primitives::TxHash,
     providers::{Provider, ProviderBuilder, RootProvider},
-    rpc::types::{TransactionReceipt, TransactionRequest},
-    signers::local::PrivateKeySigner,
+    rpc::types::TransactionRequest,
 };
-use anyhow::anyhow;
 
-use crate::utils;
+use super::AgentSigner;
 
+// eoa tx, returns the hash once broadcast
 pub async fn send_eoa_tx(
     request: TransactionRequest,
     provider: RootProvider,
-) -> Result<TransactionReceipt, anyhow::Error> {
-    // Read the private key from the environment variable
-    let private_key = utils::read_parse_env::<String>("EVM_PRIVATE_KEY");
-    let signer: PrivateKeySigner = private_key.parse().expect("parse PrivateKeySigner");
-    let wallet: EthereumWallet = EthereumWallet::from(signer.clone());
+    signer: &AgentSigner,
+) -> Result<TxHash, anyhow::Error> {
+    let wallet = signer.wallet();
 
     // Create eth signer.
     let signer = Arc::new(
@@ -28,8 +25,5 @@ pub async fn send_eoa_tx(
 
     let pending_tx = signer.send_transaction(request).await?;
 
-    pending_tx
-        .get_receipt()
-        .await
-        .map_err(|e| anyhow!(e.to_string()))
+    Ok(*pending_tx.tx_hash())
 }
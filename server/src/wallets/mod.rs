@@ -1,26 +1,167 @@
 use std::env;
 
+use crate::contracts::rpc;
 use alloy::{
-    providers::RootProvider,
+    primitives::TxKind,
+    providers::{Provider, RootProvider},
     rpc::types::{TransactionReceipt, TransactionRequest},
 };
 use anyhow::anyhow;
-use eip7702::send_7702_tx;
+use gas::FeeEstimate;
 use local::send_eoa_tx;
+use nonce::NONCE_MANAGER;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tracker::{TrackedTx, Unconfirmed};
 
+pub mod accounts;
+pub mod approvals;
 pub mod eip7702;
+pub mod erc4337;
+pub mod execution;
+pub mod gas;
+pub mod keystore;
 pub mod local;
+pub mod nonce;
+pub mod paper;
+pub mod permit;
+pub mod safe;
+pub mod signer;
+pub mod simulate;
+pub mod tracker;
+
+pub use accounts::Account;
+pub use execution::{execute, execute_after, BalanceChange, Execution, EXECUTION_MODE};
+pub use signer::{signer, signer_address, AgentSigner};
 
 pub static ACCONT_TYPE: Lazy<String> = Lazy::new(|| env::var("ACCONT_TYPE").unwrap());
 
+/// Where a transaction comes from: the chain it targets, the tool call that built it and
+/// the account it is sent from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxContext {
+    pub chain: String,
+    pub tool: String,
+    /// Named account, see `accounts`; `None` is the default account.
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+impl TxContext {
+    pub fn new(chain: &str, tool: &str) -> Self {
+        Self {
+            chain: chain.to_string(),
+            tool: tool.to_string(),
+            account: None,
+        }
+    }
+
+    pub fn with_account(mut self, account: Option<String>) -> Self {
+        self.account = account;
+        self
+    }
+}
+
 pub async fn send_tx(
     request: TransactionRequest,
     provider: RootProvider,
+    ctx: TxContext,
 ) -> Result<TransactionReceipt, anyhow::Error> {
-    match ACCONT_TYPE.as_str() {
-        "EIP7702" => send_7702_tx(request, provider).await,
-        "LOCAL" => send_eoa_tx(request, provider).await,
-        _ => Err(anyhow!("unknown account type")),
+    Ok(send_priced(request, provider, ctx).await?.0)
+}
+
+/// Like `send_tx`, also returning the fees the transaction was priced with. Operations
+/// of smart accounts are priced by the bundler and come without them.
+pub async fn send_priced(
+    request: TransactionRequest,
+    provider: RootProvider,
+    ctx: TxContext,
+) -> Result<(TransactionReceipt, Option<FeeEstimate>), anyhow::Error> {
+    // The simulation, fees and nonce have to come from the node the tx is sent to.
+    rpc::pinned(price_and_send(request, provider, ctx)).await
+}
+
+async fn price_and_send(
+    mut request: TransactionRequest,
+    provider: RootProvider,
+    ctx: TxContext,
+) -> Result<(TransactionReceipt, Option<FeeEstimate>), anyhow::Error> {
+    let account = accounts::account(ctx.account.as_deref())?;
+
+    // Smart accounts send through a bundler, which estimates and nonces the operation.
+    if account.account_type == "ERC4337" {
+        let receipt = erc4337::send_user_op(request, provider, ctx, &account).await?;
+        return Ok((receipt, None));
     }
+
+    let from = account.address();
+    let chain_id = provider.get_chain_id().await?;
+    request.from = Some(from);
+
+    // Delegated accounts run their calls through the delegate; batches and delegations
+    // already call the account itself.
+    if account.account_type == "EIP7702" && request.to != Some(TxKind::Call(from)) {
+        request = eip7702::delegated_call(&provider, from, request).await?;
+    }
+
+    // Refuse to broadcast anything that would revert.
+    let gas = simulate::simulate(&provider, &request).await?;
+    request.gas = Some(gas);
+
+    // Price it explicitly with the chain's fee strategy and caps instead of the fillers.
+    let fees = gas::estimate(&provider, &ctx.chain, &request).await?;
+    fees.apply(&mut request);
+    log::info!("{} on {}: {}", ctx.tool, ctx.chain, fees);
+
+    // Take the nonce from the shared manager so back-to-back sends don't race.
+    let nonce = NONCE_MANAGER.reserve(&provider, chain_id, from).await?;
+    request.nonce = Some(nonce);
+
+    let signer = &account.signer;
+    let result = async {
+        // The account's own authorizations are checked after the transaction used up
+        // `nonce`, so they are signed for the next one now that it is known.
+        if let Some(authorizations) = request.authorization_list.take() {
+            let mut signed = Vec::with_capacity(authorizations.len());
+            for authorization in authorizations {
+                let delegate = authorization.address;
+                signed.push(
+                    eip7702::sign_authorization(signer, chain_id, delegate, nonce + 1).await?,
+                );
+            }
+            request.authorization_list = Some(signed);
+        }
+        match account.account_type.as_str() {
+            "EIP7702" | "LOCAL" => send_eoa_tx(request.clone(), provider.clone(), signer).await,
+            _ => Err(anyhow!("unknown account type")),
+        }
+    }
+    .await;
+
+    let hash = match result {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::warn!("send_tx with nonce {} failed: {}", nonce, e);
+            if let Err(e) = NONCE_MANAGER.reconcile(&provider, chain_id, from).await {
+                log::error!("nonce reconcile failed: {}", e);
+            }
+            return Err(e);
+        }
+    };
+
+    // Record the broadcast tx so it can be monitored, sped up or cancelled. Replacing a
+    // delegation would need its authorization signed again, so those are left alone.
+    let replaceable = account.account_type == "LOCAL" && request.authorization_list.is_none();
+    tracker::save(&TrackedTx::new(hash, request, chain_id, &ctx, replaceable))
+        .map_err(|e| Unconfirmed::new(hash, format!("tracking it failed: {}", e)))?;
+    log::info!(
+        "{} sent tx {} on {} (nonce {})",
+        ctx.tool,
+        hash,
+        ctx.chain,
+        nonce
+    );
+
+    let receipt = tracker::wait_for_receipt(&provider, hash).await?;
+    Ok((receipt, Some(fees)))
 }
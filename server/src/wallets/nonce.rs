@@ -0,0 +1,120 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use alloy::{
+    primitives::Address,
+    providers::{Provider, RootProvider},
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+
+use crate::utils::DB;
+
+/// sled tree holding the next nonce to hand out per `chain_id:account`.
+const NONCE_TREE: &str = "nonces";
+
+pub static NONCE_MANAGER: Lazy<NonceManager> = Lazy::new(NonceManager::default);
+
+/// Hands out sequential nonces per (chain, account) so concurrent agent
+/// transactions (e.g. `approve` followed by `uniswap_v3_swap`) never collide.
+///
+/// The next nonce is cached in memory and mirrored to sled so it survives restarts.
+/// It is always reconciled upwards against `eth_getTransactionCount(pending)`, and
+/// reset to the on-chain value after a failed send.
+#[derive(Default)]
+pub struct NonceManager {
+    slots: Mutex<HashMap<(u64, Address), Arc<tokio::sync::Mutex<Option<u64>>>>>,
+}
+
+impl NonceManager {
+    fn slot(&self, chain_id: u64, account: Address) -> Arc<tokio::sync::Mutex<Option<u64>>> {
+        self.slots
+            .lock()
+            .unwrap()
+            .entry((chain_id, account))
+            .or_default()
+            .clone()
+    }
+
+    /// Reserve the next nonce for `account` on the provider's chain.
+    pub async fn reserve(
+        &self,
+        provider: &RootProvider,
+        chain_id: u64,
+        account: Address,
+    ) -> Result<u64, anyhow::Error> {
+        let slot = self.slot(chain_id, account);
+        let mut next = slot.lock().await;
+
+        let cached = match *next {
+            Some(nonce) => Some(nonce),
+            None => load_nonce(chain_id, account)?,
+        };
+        let on_chain = pending_nonce(provider, account).await?;
+
+        // Never hand out a nonce the chain has already seen.
+        let nonce = cached.map_or(on_chain, |cached| cached.max(on_chain));
+        *next = Some(nonce + 1);
+        store_nonce(chain_id, account, nonce + 1)?;
+
+        log::debug!(
+            "reserved nonce {} for {} on chain {}",
+            nonce,
+            account,
+            chain_id
+        );
+        Ok(nonce)
+    }
+
+    /// Reset the cached nonce to the chain's pending transaction count.
+    ///
+    /// Called when a send fails, so a nonce that was never broadcast is handed out again.
+    pub async fn reconcile(
+        &self,
+        provider: &RootProvider,
+        chain_id: u64,
+        account: Address,
+    ) -> Result<u64, anyhow::Error> {
+        let slot = self.slot(chain_id, account);
+        let mut next = slot.lock().await;
+
+        let on_chain = pending_nonce(provider, account).await?;
+        *next = Some(on_chain);
+        store_nonce(chain_id, account, on_chain)?;
+
+        log::info!(
+            "reconciled nonce for {} on chain {} to {}",
+            account,
+            chain_id,
+            on_chain
+        );
+        Ok(on_chain)
+    }
+}
+
+async fn pending_nonce(provider: &RootProvider, account: Address) -> Result<u64, anyhow::Error> {
+    provider
+        .get_transaction_count(account)
+        .pending()
+        .await
+        .map_err(|e| anyhow!("get_transaction_count error: {}", e))
+}
+
+fn nonce_key(chain_id: u64, account: Address) -> String {
+    format!("{}:{}", chain_id, account)
+}
+
+fn load_nonce(chain_id: u64, account: Address) -> Result<Option<u64>, anyhow::Error> {
+    let tree = DB.open_tree(NONCE_TREE)?;
+    Ok(tree
+        .get(nonce_key(chain_id, account))?
+        .and_then(|v| <[u8; 8]>::try_from(v.as_ref()).ok().map(u64::from_be_bytes)))
+}
+
+fn store_nonce(chain_id: u64, account: Address, nonce: u64) -> Result<(), anyhow::Error> {
+    let tree = DB.open_tree(NONCE_TREE)?;
+    tree.insert(nonce_key(chain_id, account), nonce.to_be_bytes().to_vec())?;
+    Ok(())
+}
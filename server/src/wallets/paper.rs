@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use alloy::primitives::{Address, I256};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    accounts,
+    execution::{BalanceChange, ExecutionMode},
+    TxContext,
+};
+use crate::{
+    contracts::{common::make_provider, erc20::balance_of_erc20, eth::eth_balance},
+    utils::DB,
+};
+
+/// sled tree holding simulated balances per `chain:account:token`.
+const LEDGER_TREE: &str = "paper_ledger";
+/// sled tree holding every paper fill, keyed by id.
+const FILLS_TREE: &str = "paper_fills";
+
+/// A paper trade applied to the simulated ledger instead of being broadcast.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaperFill {
+    pub id: u64,
+    pub mode: ExecutionMode,
+    pub chain: String,
+    pub tool: String,
+    pub account: Address,
+    pub changes: Vec<BalanceChange>,
+    /// Ledger balances of the touched tokens after the fill.
+    pub balances: HashMap<Address, I256>,
+    pub timestamp: i64,
+}
+
+fn ledger_key(chain: &str, account: Address, token: Address) -> String {
+    format!("{}:{}:{}", chain, account, token)
+}
+
+/// Current paper balance, seeded from the live chain on first use.
+pub async fn paper_balance(
+    chain: &str,
+    account: Address,
+    token: Address,
+) -> Result<I256, anyhow::Error> {
+    let tree = DB.open_tree(LEDGER_TREE)?;
+    if let Some(v) = tree.get(ledger_key(chain, account, token))? {
+        return Ok(serde_json::from_slice(&v)?);
+    }
+
+    let live = if token == Address::ZERO {
+        eth_balance(account, chain.to_string()).await?
+    } else {
+        balance_of_erc20(account, token, chain.to_string()).await?
+    };
+    Ok(I256::from_raw(live))
+}
+
+/// Apply `changes` to the paper ledger for the account in `ctx`.
+pub async fn apply(
+    ctx: &TxContext,
+    changes: Vec<BalanceChange>,
+) -> Result<PaperFill, anyhow::Error> {
+    let provider = make_provider(ctx.chain.clone())?;
+    let account = accounts::account(ctx.account.as_deref())?
+        .sender(&provider)
+        .await?;
+
+    let mut balances = HashMap::new();
+    for change in &changes {
+        let current = match balances.get(&change.token) {
+            Some(balance) => *balance,
+            None => paper_balance(&ctx.chain, account, change.token).await?,
+        };
+        let updated = current + change.delta;
+        if updated.is_negative() {
+            return Err(anyhow!(
+                "insufficient paper balance of {} on {}: have {}, change {}",
+                change.token,
+                ctx.chain,
+                current,
+                change.delta
+            ));
+        }
+        balances.insert(change.token, updated);
+    }
+
+    let ledger = DB.open_tree(LEDGER_TREE)?;
+    for (token, balance) in &balances {
+        ledger.insert(
+            ledger_key(&ctx.chain, account, *token),
+            serde_json::to_vec(balance)?,
+        )?;
+    }
+
+    let fill = PaperFill {
+        id: DB.generate_id()?,
+        mode: ExecutionMode::Paper,
+        chain: ctx.chain.clone(),
+        tool: ctx.tool.clone(),
+        account,
+        changes,
+        balances,
+        timestamp: chrono::Utc::now().timestamp(),
+    };
+    let fills = DB.open_tree(FILLS_TREE)?;
+    fills.insert(fill.id.to_be_bytes().to_vec(), serde_json::to_vec(&fill)?)?;
+
+    log::info!(
+        "paper fill #{} for {} on {}: {:?}",
+        fill.id,
+        fill.tool,
+        fill.chain,
+        fill.changes
+    );
+    Ok(fill)
+}
+
+/// All paper fills, oldest first.
+pub fn list_fills() -> Result<Vec<PaperFill>, anyhow::Error> {
+    let fills = DB.open_tree(FILLS_TREE)?;
+    Ok(fills
+        .iter()
+        .values()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| serde_json::from_slice(&v).ok())
+        .collect())
+}
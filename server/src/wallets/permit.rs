@@ -0,0 +1,215 @@
+use alloy::{
+    primitives::{address, Address, Bytes, B256, U256},
+    signers::{Signature, Signer},
+    sol,
+    sol_types::{Eip712Domain, SolStruct},
+};
+use serde::{Deserialize, Serialize};
+
+use super::AgentSigner;
+use crate::contracts::abi::IPermit2::{PermitBatch, PermitSingle};
+
+/// Canonical Permit2 deployment, at the same address on every chain.
+pub const PERMIT2_ADDRESS: Address = address!("000000000022D473030F116dDEE9F6B43aC78BA3");
+
+sol! {
+    /// EIP-2612 `permit` message.
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    struct Permit {
+        address owner;
+        address spender;
+        uint256 value;
+        uint256 nonce;
+        uint256 deadline;
+    }
+}
+
+/// EIP-712 domain of an EIP-2612 token.
+pub fn erc2612_domain(
+    name: String,
+    version: String,
+    chain_id: u64,
+    token: Address,
+) -> Eip712Domain {
+    Eip712Domain::new(
+        Some(name.into()),
+        Some(version.into()),
+        Some(U256::from(chain_id)),
+        Some(token),
+        None,
+    )
+}
+
+/// EIP-712 domain of Permit2; it has no version.
+pub fn permit2_domain(chain_id: u64) -> Eip712Domain {
+    Eip712Domain::new(
+        Some("Permit2".into()),
+        None,
+        Some(U256::from(chain_id)),
+        Some(PERMIT2_ADDRESS),
+        None,
+    )
+}
+
+/// Sign the EIP-712 digest of `value` under `domain` with `signer`.
+async fn sign_typed<T: SolStruct>(
+    signer: &AgentSigner,
+    value: &T,
+    domain: &Eip712Domain,
+) -> Result<Signature, anyhow::Error> {
+    let hash: B256 = value.eip712_signing_hash(domain);
+    Ok(signer.sign_hash(&hash).await?)
+}
+
+/// Sign an EIP-2612 `permit` for the token behind `domain`.
+pub async fn sign_erc2612_permit(
+    signer: &AgentSigner,
+    permit: &Permit,
+    domain: &Eip712Domain,
+) -> Result<Signature, anyhow::Error> {
+    sign_typed(signer, permit, domain).await
+}
+
+/// Sign a Permit2 `PermitSingle`, returned as the 65-byte signature Permit2 expects.
+pub async fn sign_permit_single(
+    signer: &AgentSigner,
+    permit: &PermitSingle,
+    chain_id: u64,
+) -> Result<Bytes, anyhow::Error> {
+    let signature = sign_typed(signer, permit, &permit2_domain(chain_id)).await?;
+    Ok(Bytes::copy_from_slice(&signature.as_bytes()))
+}
+
+/// Sign a Permit2 `PermitBatch`, returned as the 65-byte signature Permit2 expects.
+pub async fn sign_permit_batch(
+    signer: &AgentSigner,
+    permit: &PermitBatch,
+    chain_id: u64,
+) -> Result<Bytes, anyhow::Error> {
+    let signature = sign_typed(signer, permit, &permit2_domain(chain_id)).await?;
+    Ok(Bytes::copy_from_slice(&signature.as_bytes()))
+}
+
+/// Split a signature into the `(v, r, s)` arguments of `permit`/`selfPermit`.
+pub fn vrs(signature: &Signature) -> (u8, B256, B256) {
+    (
+        27 + signature.v() as u8,
+        signature.r().into(),
+        signature.s().into(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::contracts::abi::IPermit2::PermitDetails;
+    use alloy::{
+        primitives::{
+            aliases::{U160, U48},
+            b256,
+        },
+        signers::local::PrivateKeySigner,
+    };
+
+    #[test]
+    fn test_type_hashes() {
+        let permit = Permit {
+            owner: Address::ZERO,
+            spender: Address::ZERO,
+            value: U256::ZERO,
+            nonce: U256::ZERO,
+            deadline: U256::ZERO,
+        };
+        assert_eq!(
+            permit.eip712_type_hash(),
+            b256!("6e71edae12b1b97f4d1f60370fef10105fa2faae0126114a169c64845d6126c9")
+        );
+
+        let single = PermitSingle {
+            details: PermitDetails {
+                token: Address::ZERO,
+                amount: U160::ZERO,
+                expiration: U48::ZERO,
+                nonce: U48::ZERO,
+            },
+            spender: Address::ZERO,
+            sigDeadline: U256::ZERO,
+        };
+        assert_eq!(
+            single.eip712_type_hash(),
+            b256!("f3841cd1ff0085026a6327b620b67997ce40f282c88a8e905a7a5626e310f3d0")
+        );
+
+        let batch = PermitBatch {
+            details: vec![single.details],
+            spender: Address::ZERO,
+            sigDeadline: U256::ZERO,
+        };
+        assert_eq!(
+            batch.eip712_type_hash(),
+            b256!("af1b0d30d2cab0380e68f0689007e3254993c596f2fdd0aaa7f4d04f79440863")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_permit_recovers_owner() {
+        let key = PrivateKeySigner::random();
+        let token = Address::repeat_byte(0x11);
+        let permit = Permit {
+            owner: key.address(),
+            spender: Address::repeat_byte(0x22),
+            value: U256::from(1_000u64),
+            nonce: U256::ZERO,
+            deadline: U256::from(u64::MAX),
+        };
+        let domain = erc2612_domain("Token".to_string(), "1".to_string(), 1, token);
+        let hash = permit.eip712_signing_hash(&domain);
+        let signature = key.sign_hash(&hash).await.unwrap();
+
+        assert_eq!(
+            signature.recover_address_from_prehash(&hash).unwrap(),
+            key.address()
+        );
+        let (v, _, _) = vrs(&signature);
+        assert!(v == 27 || v == 28);
+    }
+
+    #[tokio::test]
+    async fn test_permit2_recovers_owner() {
+        let key = PrivateKeySigner::random();
+        let signer = AgentSigner::Local(key.clone());
+        let details = PermitDetails {
+            token: Address::repeat_byte(0x11),
+            amount: U160::from(1_000u64),
+            expiration: U48::from(u32::MAX),
+            nonce: U48::ZERO,
+        };
+        let single = PermitSingle {
+            details: details.clone(),
+            spender: Address::repeat_byte(0x22),
+            sigDeadline: U256::from(u64::MAX),
+        };
+        let batch = PermitBatch {
+            details: vec![details.clone(), details],
+            spender: Address::repeat_byte(0x22),
+            sigDeadline: U256::from(u64::MAX),
+        };
+
+        let signature = sign_permit_single(&signer, &single, 1).await.unwrap();
+        let hash = single.eip712_signing_hash(&permit2_domain(1));
+        let signature = Signature::try_from(&signature[..]).unwrap();
+        assert_eq!(
+            signature.recover_address_from_prehash(&hash).unwrap(),
+            key.address()
+        );
+
+        let signature = sign_permit_batch(&signer, &batch, 1).await.unwrap();
+        assert_eq!(signature.len(), 65);
+        let hash = batch.eip712_signing_hash(&permit2_domain(1));
+        let signature = Signature::try_from(&signature[..]).unwrap();
+        assert_eq!(
+            signature.recover_address_from_prehash(&hash).unwrap(),
+            key.address()
+        );
+    }
+}
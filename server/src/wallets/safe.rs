@@ -0,0 +1,647 @@
+use std::{collections::HashSet, env, sync::Mutex};
+
+use alloy::{
+    primitives::{Address, Bytes, TxHash, TxKind, B256, U256},
+    providers::{Provider, RootProvider},
+    rpc::types::{TransactionReceipt, TransactionRequest},
+    signers::{Signature, Signer},
+    sol,
+    sol_types::{Eip712Domain, SolCall, SolStruct},
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    accounts,
+    execution::BalanceChange,
+    send_tx,
+    tracker::{self, Unconfirmed},
+    TxContext,
+};
+use crate::{
+    contracts::{abi::ISafe, common::make_provider},
+    policy,
+    utils::DB,
+};
+
+/// sled tree holding Safe proposals, keyed by proposal id.
+const PROPOSAL_TREE: &str = "safe_proposals";
+
+/// Safe the agent acts from; every transaction is proposed to its owners instead of
+/// being signed directly.
+pub static SAFE_ADDRESS: Lazy<Option<Address>> = Lazy::new(|| {
+    env::var("SAFE_ADDRESS")
+        .ok()
+        .map(|v| v.parse().expect("Cannot parse SAFE_ADDRESS env var"))
+});
+
+sol! {
+    /// The message Safe owners sign, see `Safe.getTransactionHash`.
+    #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+    struct SafeTx {
+        address to;
+        uint256 value;
+        bytes data;
+        uint8 operation;
+        uint256 safeTxGas;
+        uint256 baseGas;
+        uint256 gasPrice;
+        address gasToken;
+        address refundReceiver;
+        uint256 nonce;
+    }
+}
+
+impl SafeTx {
+    /// A plain call with no gas refund, which is how the agent's actions are proposed.
+    pub fn call(request: &TransactionRequest, nonce: U256) -> Result<Self, anyhow::Error> {
+        let to = match request.to {
+            Some(TxKind::Call(to)) => to,
+            _ => return Err(anyhow!("a Safe can't create contracts")),
+        };
+        Ok(SafeTx {
+            to,
+            value: request.value.unwrap_or_default(),
+            data: request.input.input().cloned().unwrap_or_default(),
+            operation: 0,
+            safeTxGas: U256::ZERO,
+            baseGas: U256::ZERO,
+            gasPrice: U256::ZERO,
+            gasToken: Address::ZERO,
+            refundReceiver: Address::ZERO,
+            nonce,
+        })
+    }
+}
+
+/// EIP-712 domain of a Safe (v1.3+); it has no name or version.
+pub fn safe_domain(chain_id: u64, safe: Address) -> Eip712Domain {
+    Eip712Domain::new(None, None, Some(U256::from(chain_id)), Some(safe), None)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProposalStatus {
+    Pending,
+    Executed,
+    Failed,
+}
+
+/// An owner's signature over the Safe tx hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Confirmation {
+    pub owner: Address,
+    pub signature: Bytes,
+}
+
+/// A transaction built by a tool and proposed to the Safe's owners.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafeProposal {
+    pub id: u64,
+    pub status: ProposalStatus,
+    pub ctx: TxContext,
+    pub safe: Address,
+    pub chain_id: u64,
+    pub tx: SafeTx,
+    pub safe_tx_hash: B256,
+    pub changes: Vec<BalanceChange>,
+    pub threshold: u64,
+    pub confirmations: Vec<Confirmation>,
+    pub created_at: i64,
+    pub executed_at: Option<i64>,
+    pub tx_hash: Option<TxHash>,
+    pub reason: Option<String>,
+    /// Policy spends of the tool call, released if the proposal is never executed.
+    #[serde(default)]
+    pub spends: Vec<u64>,
+    /// Proposal this one rejects by using up its nonce.
+    #[serde(default)]
+    pub rejects: Option<u64>,
+}
+
+impl SafeProposal {
+    /// Whether the proposal ended without its transaction being broadcast.
+    fn never_sent(&self) -> bool {
+        self.status == ProposalStatus::Failed && self.tx_hash.is_none()
+    }
+}
+
+fn save(proposal: &SafeProposal) -> Result<(), anyhow::Error> {
+    let tree = DB.open_tree(PROPOSAL_TREE)?;
+    tree.insert(
+        proposal.id.to_be_bytes().to_vec(),
+        serde_json::to_vec(proposal)?,
+    )?;
+    Ok(())
+}
+
+/// Load a proposal by id.
+pub fn get(id: u64) -> Result<Option<SafeProposal>, anyhow::Error> {
+    let tree = DB.open_tree(PROPOSAL_TREE)?;
+    match tree.get(id.to_be_bytes().to_vec())? {
+        Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+        None => Ok(None),
+    }
+}
+
+/// Proposals not executed yet, oldest first.
+pub fn list_pending() -> Result<Vec<SafeProposal>, anyhow::Error> {
+    let tree = DB.open_tree(PROPOSAL_TREE)?;
+    Ok(tree
+        .iter()
+        .values()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| serde_json::from_slice::<SafeProposal>(&v).ok())
+        .filter(|p| p.status == ProposalStatus::Pending)
+        .collect())
+}
+
+/// Apply `f` to a pending proposal, compare-and-swap so concurrent updates cannot
+/// overwrite each other.
+fn update(
+    id: u64,
+    f: impl FnOnce(&mut SafeProposal) -> Result<(), anyhow::Error>,
+) -> Result<SafeProposal, anyhow::Error> {
+    let tree = DB.open_tree(PROPOSAL_TREE)?;
+    let key = id.to_be_bytes().to_vec();
+    let current = tree
+        .get(&key)?
+        .ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))?;
+
+    let mut proposal: SafeProposal = serde_json::from_slice(&current)?;
+    if proposal.status != ProposalStatus::Pending {
+        return Err(anyhow!(
+            "Safe proposal #{} is already {:?}",
+            id,
+            proposal.status
+        ));
+    }
+    f(&mut proposal)?;
+
+    tree.compare_and_swap(&key, Some(current), Some(serde_json::to_vec(&proposal)?))?
+        .map_err(|_| anyhow!("Safe proposal #{} was updated concurrently", id))?;
+    Ok(proposal)
+}
+
+/// Hand the policy spends of the tool call that made proposal `id` over to it.
+pub fn hold_spends(id: u64, spends: &[u64]) -> Result<(), anyhow::Error> {
+    if spends.is_empty() {
+        return Ok(());
+    }
+    let tree = DB.open_tree(PROPOSAL_TREE)?;
+    let key = id.to_be_bytes().to_vec();
+    let current = tree
+        .get(&key)?
+        .ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))?;
+    let mut proposal: SafeProposal = serde_json::from_slice(&current)?;
+    proposal.spends.extend_from_slice(spends);
+    tree.compare_and_swap(&key, Some(current), Some(serde_json::to_vec(&proposal)?))?
+        .map_err(|_| anyhow!("Safe proposal #{} was updated concurrently", id))?;
+    // Already closed before the spends arrived.
+    if proposal.never_sent() {
+        policy::release_all(spends);
+    }
+    Ok(())
+}
+
+/// Nonce for a new proposal: after the Safe's on-chain nonce and any proposal still
+/// waiting for signatures.
+async fn next_nonce(
+    provider: &RootProvider,
+    safe: Address,
+    chain_id: u64,
+) -> Result<U256, anyhow::Error> {
+    let instance = ISafe::ISafeInstance::new(safe, provider.clone());
+    let on_chain = instance
+        .nonce()
+        .call()
+        .await
+        .map_err(|e| anyhow!("Safe nonce error: {}", e))?
+        ._0;
+    let queued = list_pending()?
+        .into_iter()
+        .filter(|p| p.safe == safe && p.chain_id == chain_id && p.tx.nonce >= on_chain)
+        .map(|p| p.tx.nonce + U256::from(1u64))
+        .max();
+    Ok(queued.unwrap_or(on_chain).max(on_chain))
+}
+
+async fn owners_and_threshold(
+    provider: &RootProvider,
+    safe: Address,
+) -> Result<(Vec<Address>, u64), anyhow::Error> {
+    let instance = ISafe::ISafeInstance::new(safe, provider.clone());
+    let owners = instance
+        .getOwners()
+        .call()
+        .await
+        .map_err(|e| anyhow!("Safe getOwners error: {}", e))?
+        ._0;
+    let threshold = instance
+        .getThreshold()
+        .call()
+        .await
+        .map_err(|e| anyhow!("Safe getThreshold error: {}", e))?
+        ._0;
+    Ok((owners, threshold.to::<u64>()))
+}
+
+/// Owner behind a Safe signature over `safe_tx_hash`.
+///
+/// Accepts plain ECDSA signatures of the hash (`v` 27/28) and `eth_sign` signatures
+/// of it, which Safe marks with `v` 31/32.
+pub fn recover_owner(safe_tx_hash: B256, signature: &[u8]) -> Result<Address, anyhow::Error> {
+    if signature.len() != 65 {
+        return Err(anyhow!("Safe signatures must be 65 bytes"));
+    }
+    match signature[64] {
+        27 | 28 => {
+            let signature = Signature::from_raw(signature)?;
+            Ok(signature.recover_address_from_prehash(&safe_tx_hash)?)
+        }
+        31 | 32 => {
+            let mut raw = signature.to_vec();
+            raw[64] -= 4;
+            let signature = Signature::from_raw(&raw)?;
+            Ok(signature.recover_address_from_msg(safe_tx_hash)?)
+        }
+        v => Err(anyhow!("unsupported Safe signature type v={}", v)),
+    }
+}
+
+/// Concatenate confirmations sorted by owner, as `execTransaction` requires.
+pub fn pack_signatures(confirmations: &[Confirmation]) -> Bytes {
+    let mut sorted = confirmations.to_vec();
+    sorted.sort_by_key(|c| c.owner);
+    sorted
+        .iter()
+        .flat_map(|c| c.signature.iter().copied())
+        .collect::<Vec<u8>>()
+        .into()
+}
+
+/// Propose `request` to the configured Safe.
+///
+/// The agent's signer confirms it right away when it is one of the owners, and the
+/// transaction is executed if that already meets the threshold.
+pub async fn propose(
+    request: TransactionRequest,
+    provider: &RootProvider,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+) -> Result<SafeProposal, anyhow::Error> {
+    let safe = (*SAFE_ADDRESS).ok_or_else(|| anyhow!("SAFE_ADDRESS is not set"))?;
+    let chain_id = provider.get_chain_id().await?;
+    let nonce = next_nonce(provider, safe, chain_id).await?;
+    propose_at(request, provider, ctx, changes, safe, nonce, None).await
+}
+
+/// Propose a rejection of proposal `id`: an empty call of the Safe to itself at the same
+/// nonce.
+///
+/// A pending proposal holds up every later nonce of the Safe. Once the rejection is
+/// executed the nonce is used up, the original can never run and later proposals can.
+pub async fn reject(id: u64, reason: Option<String>) -> Result<SafeProposal, anyhow::Error> {
+    let proposal = get(id)?.ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))?;
+    if proposal.status != ProposalStatus::Pending {
+        return Err(anyhow!(
+            "Safe proposal #{} is already {:?}",
+            id,
+            proposal.status
+        ));
+    }
+    let provider = make_provider(proposal.ctx.chain.clone())?;
+    let request = TransactionRequest::default().to(proposal.safe);
+    let ctx = TxContext::new(
+        &proposal.ctx.chain,
+        &format!("reject:{}", proposal.ctx.tool),
+    )
+    .with_account(proposal.ctx.account.clone());
+    log::info!(
+        "rejecting Safe proposal #{}: {}",
+        id,
+        reason.as_deref().unwrap_or("no reason given")
+    );
+    propose_at(
+        request,
+        &provider,
+        ctx,
+        Vec::new(),
+        proposal.safe,
+        proposal.tx.nonce,
+        Some(id),
+    )
+    .await
+}
+
+async fn propose_at(
+    request: TransactionRequest,
+    provider: &RootProvider,
+    ctx: TxContext,
+    changes: Vec<BalanceChange>,
+    safe: Address,
+    nonce: U256,
+    rejects: Option<u64>,
+) -> Result<SafeProposal, anyhow::Error> {
+    let chain_id = provider.get_chain_id().await?;
+    let tx = SafeTx::call(&request, nonce)?;
+    let safe_tx_hash = tx.eip712_signing_hash(&safe_domain(chain_id, safe));
+    let (owners, threshold) = owners_and_threshold(provider, safe).await?;
+
+    let proposal = SafeProposal {
+        id: DB.generate_id()?,
+        status: ProposalStatus::Pending,
+        ctx,
+        safe,
+        chain_id,
+        tx,
+        safe_tx_hash,
+        changes,
+        threshold,
+        confirmations: Vec::new(),
+        created_at: chrono::Utc::now().timestamp(),
+        executed_at: None,
+        tx_hash: None,
+        reason: None,
+        spends: Vec::new(),
+        rejects,
+    };
+    save(&proposal)?;
+    log::info!(
+        "proposed {} on {} to Safe {} as #{} (nonce {})",
+        proposal.ctx.tool,
+        proposal.ctx.chain,
+        safe,
+        proposal.id,
+        nonce
+    );
+
+    let account = accounts::account(proposal.ctx.account.as_deref())?;
+    if !owners.contains(&account.address()) {
+        return Ok(proposal);
+    }
+    sign(proposal.id).await
+}
+
+/// Confirm a proposal with the signer of the account that proposed it.
+pub async fn sign(id: u64) -> Result<SafeProposal, anyhow::Error> {
+    let proposal = get(id)?.ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))?;
+    let account = accounts::account(proposal.ctx.account.as_deref())?;
+    let signature = account.signer.sign_hash(&proposal.safe_tx_hash).await?;
+    confirm(id, Bytes::copy_from_slice(&signature.as_bytes())).await
+}
+
+/// Store an owner's signature, executing the proposal once it meets the threshold.
+pub async fn confirm(id: u64, signature: Bytes) -> Result<SafeProposal, anyhow::Error> {
+    let proposal = get(id)?.ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))?;
+    let owner = recover_owner(proposal.safe_tx_hash, &signature)?;
+
+    let provider = make_provider(proposal.ctx.chain.clone())?;
+    let (owners, threshold) = owners_and_threshold(&provider, proposal.safe).await?;
+    if !owners.contains(&owner) {
+        return Err(anyhow!(
+            "{} is not an owner of Safe {}",
+            owner,
+            proposal.safe
+        ));
+    }
+
+    let proposal = update(id, |p| {
+        if p.confirmations.iter().any(|c| c.owner == owner) {
+            return Err(anyhow!("{} already confirmed Safe proposal #{}", owner, id));
+        }
+        p.confirmations.push(Confirmation { owner, signature });
+        p.threshold = threshold;
+        Ok(())
+    })?;
+    log::info!(
+        "Safe proposal #{} confirmed by {} ({}/{})",
+        id,
+        owner,
+        proposal.confirmations.len(),
+        threshold
+    );
+
+    if (proposal.confirmations.len() as u64) < threshold {
+        return Ok(proposal);
+    }
+    execute(id).await?;
+    get(id)?.ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))
+}
+
+/// Proposals being executed by this process, so none is sent twice at once.
+static EXECUTING: Lazy<Mutex<HashSet<u64>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Claim on a proposal in `EXECUTING`, given up when dropped.
+struct Executing(u64);
+
+impl Executing {
+    fn claim(id: u64) -> Result<Self, anyhow::Error> {
+        if !EXECUTING.lock().unwrap().insert(id) {
+            return Err(anyhow!("Safe proposal #{} is already being executed", id));
+        }
+        Ok(Self(id))
+    }
+}
+
+impl Drop for Executing {
+    fn drop(&mut self) {
+        EXECUTING.lock().unwrap().remove(&self.0);
+    }
+}
+
+/// Record that proposal `id` was executed in `receipt`, and close the other proposals
+/// at its nonce: they can never run now.
+fn executed(id: u64, receipt: &TransactionReceipt) -> Result<(), anyhow::Error> {
+    let proposal = update(id, |p| {
+        p.status = ProposalStatus::Executed;
+        p.executed_at = Some(chrono::Utc::now().timestamp());
+        p.tx_hash = Some(receipt.transaction_hash);
+        p.reason = None;
+        Ok(())
+    })?;
+    log::info!(
+        "Safe proposal #{} executed in {}",
+        id,
+        receipt.transaction_hash
+    );
+
+    let superseded = list_pending()?.into_iter().filter(|p| {
+        p.safe == proposal.safe
+            && p.chain_id == proposal.chain_id
+            && p.tx.nonce == proposal.tx.nonce
+    });
+    for other in superseded {
+        let reason = if proposal.rejects == Some(other.id) {
+            format!("rejected by Safe proposal #{}", id)
+        } else {
+            format!("nonce {} was used by Safe proposal #{}", other.tx.nonce, id)
+        };
+        let closed = update(other.id, |p| {
+            p.status = ProposalStatus::Failed;
+            p.reason = Some(reason);
+            Ok(())
+        })?;
+        policy::release_all(&closed.spends);
+        log::info!("Safe proposal #{} closed: {:?}", closed.id, closed.reason);
+    }
+    Ok(())
+}
+
+/// Send `execTransaction` for a proposal with enough confirmations.
+///
+/// The proposal is only marked executed once its receipt is in. If the receipt doesn't
+/// arrive in time the proposal stays pending with its tx hash, and executing it again
+/// picks that transaction up instead of sending another.
+pub async fn execute(id: u64) -> Result<TransactionReceipt, anyhow::Error> {
+    let _claim = Executing::claim(id)?;
+    let proposal = get(id)?.ok_or_else(|| anyhow!("unknown Safe proposal #{}", id))?;
+    if proposal.status != ProposalStatus::Pending {
+        return Err(anyhow!(
+            "Safe proposal #{} is already {:?}",
+            id,
+            proposal.status
+        ));
+    }
+    let provider = make_provider(proposal.ctx.chain.clone())?;
+
+    // The tracker may have sped up or cancelled the earlier send; follow its replacements.
+    if let Some(hash) = proposal.tx_hash {
+        match tracker::included(&provider, hash).await? {
+            Some((receipt, false)) if receipt.status() => {
+                executed(id, &receipt)?;
+                return Ok(receipt);
+            }
+            // Reverted or cancelled, so the Safe nonce is still free; send it again below.
+            Some(_) => {}
+            None => {
+                return Err(anyhow!(
+                    "Safe proposal #{} was sent in {}, which is still pending",
+                    id,
+                    hash
+                ))
+            }
+        }
+    }
+
+    let (_, threshold) = owners_and_threshold(&provider, proposal.safe).await?;
+    if (proposal.confirmations.len() as u64) < threshold {
+        return Err(anyhow!(
+            "Safe proposal #{} has {} of {} confirmations",
+            id,
+            proposal.confirmations.len(),
+            threshold
+        ));
+    }
+
+    let tx = &proposal.tx;
+    let call = ISafe::execTransactionCall {
+        to: tx.to,
+        value: tx.value,
+        data: tx.data.clone(),
+        operation: tx.operation,
+        safeTxGas: tx.safeTxGas,
+        baseGas: tx.baseGas,
+        gasPrice: tx.gasPrice,
+        gasToken: tx.gasToken,
+        refundReceiver: tx.refundReceiver,
+        signatures: pack_signatures(&proposal.confirmations),
+    };
+    let request = TransactionRequest::default()
+        .to(proposal.safe)
+        .input(call.abi_encode().into());
+    let ctx = TxContext::new(&proposal.ctx.chain, &format!("safe:{}", proposal.ctx.tool))
+        .with_account(proposal.ctx.account.clone());
+
+    match send_tx(request, provider, ctx).await {
+        Ok(receipt) => {
+            executed(id, &receipt)?;
+            Ok(receipt)
+        }
+        Err(e) => {
+            let reason = e.to_string();
+            match e.downcast_ref::<Unconfirmed>() {
+                // Broadcast: keep it pending with the hash so it isn't sent twice.
+                Some(unconfirmed) => {
+                    let hash = unconfirmed.hash;
+                    update(id, |p| {
+                        p.tx_hash = Some(hash);
+                        p.reason = Some(reason);
+                        Ok(())
+                    })?;
+                }
+                None => {
+                    let failed = update(id, |p| {
+                        p.status = ProposalStatus::Failed;
+                        p.tx_hash = None;
+                        p.reason = Some(reason);
+                        Ok(())
+                    })?;
+                    policy::release_all(&failed.spends);
+                }
+            }
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy::{
+        primitives::{address, b256},
+        signers::local::PrivateKeySigner,
+    };
+
+    #[test]
+    fn test_safe_tx_type_hash() {
+        let request = TransactionRequest::default().to(Address::ZERO);
+        let tx = SafeTx::call(&request, U256::ZERO).unwrap();
+        assert_eq!(
+            tx.eip712_type_hash(),
+            b256!("bb8310d486368db6bd6f849402fdd73ad53d316b5a4b2644ad6efe0f941286d8")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recover_owner() {
+        let owner = PrivateKeySigner::random();
+        let hash = B256::repeat_byte(0x42);
+
+        let signature = owner.sign_hash(&hash).await.unwrap();
+        assert_eq!(
+            recover_owner(hash, &signature.as_bytes()).unwrap(),
+            owner.address()
+        );
+
+        // eth_sign signatures are marked with v + 4.
+        let mut eth_sign = owner
+            .sign_message(hash.as_slice())
+            .await
+            .unwrap()
+            .as_bytes();
+        eth_sign[64] += 4;
+        assert_eq!(recover_owner(hash, &eth_sign).unwrap(), owner.address());
+
+        assert!(recover_owner(hash, &[0u8; 64]).is_err());
+    }
+
+    #[test]
+    fn test_pack_signatures_sorted_by_owner() {
+        let confirmations = [
+            Confirmation {
+                owner: address!("0000000000000000000000000000000000000002"),
+                signature: Bytes::from_static(&[2; 65]),
+            },
+            Confirmation {
+                owner: address!("0000000000000000000000000000000000000001"),
+                signature: Bytes::from_static(&[1; 65]),
+            },
+        ];
+        let packed = pack_signatures(&confirmations);
+        assert_eq!(packed.len(), 130);
+        assert_eq!(packed[0], 1);
+        assert_eq!(packed[65], 2);
+    }
+}
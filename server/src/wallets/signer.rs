@@ -0,0 +1,241 @@
+use std::env;
+use std::sync::RwLock;
+
+use alloy::{
+    consensus::SignableTransaction,
+    network::{EthereumWallet, TxSigner},
+    primitives::{Address, ChainId, B256},
+    signers::{
+        local::{coins_bip39::English, MnemonicBuilder, PrivateKeySigner},
+        Signature, Signer,
+    },
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+/// Default BIP-44 derivation path used when `MNEMONIC_PATH` is not set.
+const DEFAULT_DERIVATION_PATH: &str = "m/44'/60'/0'/0/0";
+
+/// Selects where the signing key comes from: ENV, KEYSTORE, MNEMONIC or REMOTE.
+pub static SIGNER_TYPE: Lazy<String> =
+    Lazy::new(|| env::var("SIGNER_TYPE").unwrap_or_else(|_| "ENV".to_string()));
+
+/// The process-wide signer, loaded on first use.
+static SIGNER: Lazy<RwLock<Option<AgentSigner>>> = Lazy::new(|| RwLock::new(None));
+
+/// A signing identity used by every transaction path in `contracts` and `wallets`.
+#[derive(Debug, Clone)]
+pub enum AgentSigner {
+    /// A key held in process memory (env key, keystore or mnemonic).
+    Local(PrivateKeySigner),
+    /// A key held by an external signing service.
+    Remote(RemoteSigner),
+}
+
+impl AgentSigner {
+    /// Load the signer configured by `SIGNER_TYPE`.
+    pub fn from_env() -> Result<Self, anyhow::Error> {
+        match SIGNER_TYPE.as_str() {
+            "ENV" => {
+                let private_key = utils::read_parse_env::<String>("EVM_PRIVATE_KEY");
+                let signer: PrivateKeySigner = private_key
+                    .parse()
+                    .map_err(|e| anyhow!("parse EVM_PRIVATE_KEY error: {}", e))?;
+                Ok(AgentSigner::Local(signer))
+            }
+            // Keystores are decrypted once at startup, see `keystore::unlock_at_startup`.
+            "KEYSTORE" => Err(anyhow!("keystore is locked, unlock it at startup")),
+            "MNEMONIC" => {
+                let phrase = utils::read_parse_env::<String>("MNEMONIC");
+                let path = env::var("MNEMONIC_PATH")
+                    .unwrap_or_else(|_| DEFAULT_DERIVATION_PATH.to_string());
+                let signer = MnemonicBuilder::<English>::default()
+                    .phrase(phrase)
+                    .derivation_path(&path)?
+                    .build()
+                    .map_err(|e| anyhow!("derive mnemonic signer error: {}", e))?;
+                Ok(AgentSigner::Local(signer))
+            }
+            "REMOTE" => {
+                let url = utils::read_parse_env::<String>("REMOTE_SIGNER_URL");
+                let address = utils::read_parse_env::<Address>("REMOTE_SIGNER_ADDRESS");
+                let token = env::var("REMOTE_SIGNER_TOKEN").ok();
+                Ok(AgentSigner::Remote(RemoteSigner::new(url, address, token)))
+            }
+            other => Err(anyhow!("unknown signer type: {}", other)),
+        }
+    }
+
+    /// Wrap the signer into an alloy wallet for use with `ProviderBuilder`.
+    pub fn wallet(&self) -> EthereumWallet {
+        match self {
+            AgentSigner::Local(signer) => EthereumWallet::from(signer.clone()),
+            AgentSigner::Remote(signer) => EthereumWallet::from(signer.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for AgentSigner {
+    async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<Signature> {
+        match self {
+            AgentSigner::Local(signer) => signer.sign_hash(hash).await,
+            AgentSigner::Remote(signer) => signer.sign_hash(hash).await,
+        }
+    }
+
+    fn address(&self) -> Address {
+        match self {
+            AgentSigner::Local(signer) => signer.address(),
+            AgentSigner::Remote(signer) => Signer::address(signer),
+        }
+    }
+
+    fn chain_id(&self) -> Option<ChainId> {
+        match self {
+            AgentSigner::Local(signer) => signer.chain_id(),
+            AgentSigner::Remote(signer) => signer.chain_id(),
+        }
+    }
+
+    fn set_chain_id(&mut self, chain_id: Option<ChainId>) {
+        match self {
+            AgentSigner::Local(signer) => signer.set_chain_id(chain_id),
+            AgentSigner::Remote(signer) => signer.set_chain_id(chain_id),
+        }
+    }
+}
+
+/// Request body sent to the remote signing service.
+#[derive(Debug, Serialize)]
+struct RemoteSignRequest {
+    address: Address,
+    hash: B256,
+}
+
+/// Response body returned by the remote signing service.
+#[derive(Debug, Deserialize)]
+struct RemoteSignResponse {
+    signature: String,
+}
+
+/// HTTP client for an external signer exposing `POST {url}/sign`.
+///
+/// The service receives the 32-byte digest and returns a 65-byte hex signature,
+/// so the private key never enters this process.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    url: String,
+    address: Address,
+    token: Option<String>,
+    chain_id: Option<ChainId>,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    pub fn new(url: String, address: Address, token: Option<String>) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            address,
+            token,
+            chain_id: None,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    async fn request_signature(&self, hash: &B256) -> Result<Signature, anyhow::Error> {
+        let mut request = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&RemoteSignRequest {
+                address: self.address,
+                hash: *hash,
+            });
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        let response = request.send().await?.error_for_status()?;
+        let body: RemoteSignResponse = response.json().await?;
+        let signature: Signature = body
+            .signature
+            .parse()
+            .map_err(|e| anyhow!("invalid remote signature: {}", e))?;
+
+        // Never trust the remote side blindly: the signature must recover to our address.
+        let recovered = signature.recover_address_from_prehash(hash)?;
+        if recovered != self.address {
+            return Err(anyhow!(
+                "remote signer returned signature for {}, expected {}",
+                recovered,
+                self.address
+            ));
+        }
+        Ok(signature)
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<Signature> {
+        self.request_signature(hash)
+            .await
+            .map_err(alloy::signers::Error::other)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> Option<ChainId> {
+        self.chain_id
+    }
+
+    fn set_chain_id(&mut self, chain_id: Option<ChainId>) {
+        self.chain_id = chain_id;
+    }
+}
+
+#[async_trait]
+impl TxSigner<Signature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<Signature>,
+    ) -> alloy::signers::Result<Signature> {
+        let hash = tx.signature_hash();
+        self.sign_hash(&hash).await
+    }
+}
+
+/// Return the process-wide signer, loading it from the environment on first use.
+pub fn signer() -> Result<AgentSigner, anyhow::Error> {
+    if let Some(signer) = SIGNER.read().unwrap().as_ref() {
+        return Ok(signer.clone());
+    }
+    let signer = AgentSigner::from_env()?;
+    *SIGNER.write().unwrap() = Some(signer.clone());
+    Ok(signer)
+}
+
+/// Replace the process-wide signer, e.g. with a key unlocked from a keystore.
+pub fn install_signer(signer: AgentSigner) {
+    *SIGNER.write().unwrap() = Some(signer);
+}
+
+/// Drop the process-wide signer. Local keys are zeroized when dropped.
+pub fn clear_signer() {
+    SIGNER.write().unwrap().take();
+}
+
+/// Address of the process-wide signer.
+pub fn signer_address() -> Result<Address, anyhow::Error> {
+    Ok(Signer::address(&signer()?))
+}
@@ -0,0 +1,221 @@
+use alloy::{
+    primitives::{Address, Bytes, TxKind},
+    providers::{Provider, RootProvider},
+    rpc::types::{
+        simulate::{SimBlock, SimulatePayload},
+        TransactionRequest,
+    },
+    sol_types::{decode_revert_reason, SolInterface},
+    transports::TransportError,
+};
+use anyhow::anyhow;
+use serde::Serialize;
+
+use crate::contracts::{abi::IERC20, swap::IROUTER};
+
+/// Gas estimates are padded by this percentage before being set on the request.
+const GAS_BUFFER_PERCENT: u64 = 20;
+
+/// A transaction that would revert, reported back to the agent instead of being broadcast.
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("simulation of {call} reverted: {reason}")]
+pub struct SimulationFailure {
+    /// The decoded function name, e.g. `swapExactETHForTokens`.
+    pub call: String,
+    pub to: Option<Address>,
+    /// Human readable revert reason decoded from the known ABIs.
+    pub reason: String,
+    pub revert_data: Option<Bytes>,
+}
+
+impl SimulationFailure {
+    /// JSON payload handed to the LLM as the tool error.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "error": "simulation_reverted",
+            "call": self.call,
+            "to": self.to,
+            "reason": self.reason,
+            "revert_data": self.revert_data,
+        })
+        .to_string()
+    }
+}
+
+/// Name the function being called using the ABIs the tools build calldata from.
+fn decode_call(input: &[u8]) -> String {
+    if input.is_empty() {
+        return "transfer(native)".to_string();
+    }
+    if let Ok(call) = IERC20::IERC20Calls::abi_decode(input, false) {
+        return format!("{:?}", call)
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+    }
+    if let Ok(call) = IROUTER::IROUTERCalls::abi_decode(input, false) {
+        return format!("{:?}", call)
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+    }
+    match input.get(..4) {
+        Some(selector) => format!("unknown(0x{})", alloy::hex::encode(selector)),
+        None => "unknown".to_string(),
+    }
+}
+
+/// Decode revert data against the custom errors we know, then `Error(string)`/`Panic(uint)`.
+fn decode_revert(data: &[u8]) -> Option<String> {
+    if let Ok(error) = IERC20::IERC20Errors::abi_decode(data, false) {
+        return Some(format!("{:?}", error));
+    }
+    decode_revert_reason(data)
+}
+
+fn into_failure(request: &TransactionRequest, e: TransportError) -> anyhow::Error {
+    let Some(payload) = e.as_error_resp() else {
+        // Transport problems are not reverts; surface them as plain errors.
+        return anyhow!("simulation rpc error: {}", e);
+    };
+
+    let revert_data = payload.as_revert_data();
+    let reason = revert_data
+        .as_ref()
+        .and_then(|data| decode_revert(data))
+        .unwrap_or_else(|| payload.message.to_string());
+    failure(request, reason, revert_data)
+}
+
+fn failure(
+    request: &TransactionRequest,
+    reason: String,
+    revert_data: Option<Bytes>,
+) -> anyhow::Error {
+    let to = match request.to {
+        Some(TxKind::Call(to)) => Some(to),
+        _ => None,
+    };
+
+    SimulationFailure {
+        call: decode_call(
+            request
+                .input
+                .input()
+                .map(|i| i.as_ref())
+                .unwrap_or_default(),
+        ),
+        to,
+        reason,
+        revert_data,
+    }
+    .into()
+}
+
+/// Dry-run `request` with `eth_call` and `eth_estimateGas` before it is signed.
+///
+/// Returns the padded gas limit, or a `SimulationFailure` if execution would revert.
+pub async fn simulate(
+    provider: &RootProvider,
+    request: &TransactionRequest,
+) -> Result<u64, anyhow::Error> {
+    provider
+        .call(request.clone())
+        .await
+        .map_err(|e| into_failure(request, e))?;
+
+    let gas = provider
+        .estimate_gas(request.clone())
+        .await
+        .map_err(|e| into_failure(request, e))?;
+
+    Ok(gas + gas * GAS_BUFFER_PERCENT / 100)
+}
+
+/// Dry-run `requests` in order within one block with `eth_simulateV1`, for a transaction
+/// that needs others not sent yet (e.g. a swap and the approve of its input token).
+///
+/// Returns the padded gas limit of each request, or a `SimulationFailure` for the first
+/// one that would revert.
+pub async fn simulate_bundle(
+    provider: &RootProvider,
+    requests: &[TransactionRequest],
+) -> Result<Vec<u64>, anyhow::Error> {
+    let payload = SimulatePayload {
+        block_state_calls: vec![SimBlock {
+            block_overrides: None,
+            state_overrides: None,
+            calls: requests.to_vec(),
+        }],
+        trace_transfers: false,
+        validation: false,
+        return_full_transactions: false,
+    };
+    let blocks = provider
+        .simulate(&payload)
+        .await
+        .map_err(|e| anyhow!("simulation rpc error: {}", e))?;
+    let results = blocks
+        .into_iter()
+        .next()
+        .map(|block| block.calls)
+        .unwrap_or_default();
+    if results.len() != requests.len() {
+        return Err(anyhow!(
+            "eth_simulateV1 returned {} results for {} calls",
+            results.len(),
+            requests.len()
+        ));
+    }
+
+    requests
+        .iter()
+        .zip(results)
+        .map(|(request, result)| {
+            if result.status {
+                let gas = result.gas_used;
+                return Ok(gas + gas * GAS_BUFFER_PERCENT / 100);
+            }
+            let reason = decode_revert(&result.return_data)
+                .or(result.error.map(|error| error.message))
+                .unwrap_or_else(|| "execution reverted".to_string());
+            Err(failure(request, reason, Some(result.return_data)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy::{
+        primitives::U256,
+        sol_types::{SolCall, SolError},
+    };
+
+    #[test]
+    fn test_decode_call() {
+        let input = IERC20::transferCall {
+            to: Address::ZERO,
+            amount: U256::from(1),
+        }
+        .abi_encode();
+        assert_eq!(decode_call(&input), "transfer");
+        assert_eq!(decode_call(&[]), "transfer(native)");
+        assert_eq!(
+            decode_call(&[0xde, 0xad, 0xbe, 0xef]),
+            "unknown(0xdeadbeef)"
+        );
+    }
+
+    #[test]
+    fn test_decode_revert_string() {
+        // Error(string) with "UniswapV2Router: EXPIRED"
+        let data = alloy::sol_types::Revert::from("UniswapV2Router: EXPIRED").abi_encode();
+        assert_eq!(
+            decode_revert(&data).as_deref(),
+            Some("revert: UniswapV2Router: EXPIRED")
+        );
+    }
+}
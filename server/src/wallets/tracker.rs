@@ -0,0 +1,432 @@
+use std::{env, time::Duration};
+
+use alloy::{
+    consensus::Transaction,
+    network::TransactionBuilder,
+    primitives::{Address, TxHash, U256},
+    providers::{Provider, ProviderBuilder, RootProvider},
+    rpc::types::{TransactionReceipt, TransactionRequest},
+};
+use anyhow::anyhow;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{accounts, gas, TxContext};
+use crate::{
+    contracts::{common::make_provider, rpc},
+    utils::DB,
+};
+
+/// sled tree holding every submitted transaction, keyed by hash.
+const TX_TREE: &str = "txs";
+
+/// How often pending transactions are polled for inclusion.
+pub static TX_POLL_INTERVAL: Lazy<Duration> = Lazy::new(|| {
+    Duration::from_secs(
+        env::var("TX_POLL_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5),
+    )
+});
+
+/// A transaction still pending after this long is considered stuck and gets its fees bumped.
+pub static TX_STUCK_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+    Duration::from_secs(
+        env::var("TX_STUCK_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(180),
+    )
+});
+
+/// How long `send_tx` waits for inclusion before handing back to the caller.
+pub static TX_WAIT_TIMEOUT: Lazy<Duration> = Lazy::new(|| {
+    Duration::from_secs(
+        env::var("TX_WAIT_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(600),
+    )
+});
+
+/// Percentage added to both fee fields on every replacement. Nodes require at least 10%.
+pub static TX_FEE_BUMP_PERCENT: Lazy<u128> = Lazy::new(|| {
+    env::var("TX_FEE_BUMP_PERCENT")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20)
+});
+
+/// Upper bound on automatic fee bumps for a single nonce.
+const MAX_AUTO_BUMPS: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    Pending,
+    Confirmed,
+    Failed,
+    Replaced,
+    Cancelled,
+}
+
+/// A transaction that was broadcast but whose outcome is not known yet; it may still be
+/// included, so callers must not treat it as unsent.
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("transaction {hash} was broadcast but {reason}")]
+pub struct Unconfirmed {
+    /// Transaction hash, or user operation hash for ERC-4337 accounts.
+    pub hash: TxHash,
+    pub reason: String,
+}
+
+impl Unconfirmed {
+    pub fn new(hash: TxHash, reason: impl ToString) -> Self {
+        Self {
+            hash,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// A submitted transaction together with everything needed to replace it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedTx {
+    pub hash: TxHash,
+    pub chain: String,
+    pub chain_id: u64,
+    pub from: Address,
+    pub nonce: u64,
+    pub max_fee_per_gas: Option<u128>,
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// The rig tool call that produced this transaction.
+    pub tool: String,
+    /// Named account that signed it; `None` is the default account.
+    #[serde(default)]
+    pub account: Option<String>,
+    pub status: TxStatus,
+    pub replaces: Option<TxHash>,
+    pub replaced_by: Option<TxHash>,
+    /// Only plain EOA transactions can be re-signed with bumped fees.
+    pub replaceable: bool,
+    pub bumps: u32,
+    pub submitted_at: i64,
+    pub updated_at: i64,
+    pub block_number: Option<u64>,
+    pub request: TransactionRequest,
+}
+
+impl TrackedTx {
+    pub fn new(
+        hash: TxHash,
+        request: TransactionRequest,
+        chain_id: u64,
+        ctx: &TxContext,
+        replaceable: bool,
+    ) -> Self {
+        let now = chrono::Utc::now().timestamp();
+        Self {
+            hash,
+            chain: ctx.chain.clone(),
+            chain_id,
+            from: request.from.unwrap_or_default(),
+            nonce: request.nonce.unwrap_or_default(),
+            max_fee_per_gas: request.max_fee_per_gas,
+            max_priority_fee_per_gas: request.max_priority_fee_per_gas,
+            tool: ctx.tool.clone(),
+            account: ctx.account.clone(),
+            status: TxStatus::Pending,
+            replaces: None,
+            replaced_by: None,
+            replaceable,
+            bumps: 0,
+            submitted_at: now,
+            updated_at: now,
+            block_number: None,
+            request,
+        }
+    }
+
+    fn is_cancellation(&self) -> bool {
+        self.tool.starts_with("cancel:")
+    }
+
+    fn is_stuck(&self) -> bool {
+        let age = chrono::Utc::now().timestamp() - self.submitted_at;
+        self.status == TxStatus::Pending && age >= TX_STUCK_TIMEOUT.as_secs() as i64
+    }
+}
+
+/// Persist a transaction record.
+pub fn save(tx: &TrackedTx) -> Result<(), anyhow::Error> {
+    let tree = DB.open_tree(TX_TREE)?;
+    tree.insert(tx.hash.to_string(), serde_json::to_vec(tx)?)?;
+    Ok(())
+}
+
+/// Load a transaction record by hash.
+pub fn get(hash: TxHash) -> Result<Option<TrackedTx>, anyhow::Error> {
+    let tree = DB.open_tree(TX_TREE)?;
+    match tree.get(hash.to_string())? {
+        Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+        None => Ok(None),
+    }
+}
+
+/// All transactions still waiting for inclusion, oldest first.
+pub fn list_pending() -> Result<Vec<TrackedTx>, anyhow::Error> {
+    let tree = DB.open_tree(TX_TREE)?;
+    let mut pending: Vec<TrackedTx> = tree
+        .iter()
+        .values()
+        .filter_map(|v| v.ok())
+        .filter_map(|v| serde_json::from_slice::<TrackedTx>(&v).ok())
+        .filter(|tx| tx.status == TxStatus::Pending)
+        .collect();
+    pending.sort_by_key(|tx| tx.submitted_at);
+    Ok(pending)
+}
+
+fn set_status(
+    hash: TxHash,
+    status: TxStatus,
+    block_number: Option<u64>,
+) -> Result<(), anyhow::Error> {
+    if let Some(mut tx) = get(hash)? {
+        tx.status = status;
+        tx.block_number = block_number.or(tx.block_number);
+        tx.updated_at = chrono::Utc::now().timestamp();
+        save(&tx)?;
+    }
+    Ok(())
+}
+
+/// Every hash sharing this transaction's nonce, from the original to the latest replacement.
+fn replacement_chain(hash: TxHash) -> Result<Vec<TrackedTx>, anyhow::Error> {
+    let mut root = get(hash)?.ok_or_else(|| anyhow!("unknown transaction {}", hash))?;
+    while let Some(previous) = root.replaces.and_then(|h| get(h).ok().flatten()) {
+        root = previous;
+    }
+
+    let mut chain = vec![root];
+    while let Some(next) = chain
+        .last()
+        .and_then(|tx| tx.replaced_by)
+        .and_then(|h| get(h).ok().flatten())
+    {
+        chain.push(next);
+    }
+    Ok(chain)
+}
+
+/// Check whether any transaction for this nonce was included and update the records.
+async fn check_inclusion(
+    provider: &RootProvider,
+    hash: TxHash,
+) -> Result<Option<TransactionReceipt>, anyhow::Error> {
+    let chain = replacement_chain(hash)?;
+    for candidate in &chain {
+        if let Some(receipt) = provider.get_transaction_receipt(candidate.hash).await? {
+            let status = if candidate.is_cancellation() {
+                TxStatus::Cancelled
+            } else if receipt.status() {
+                TxStatus::Confirmed
+            } else {
+                TxStatus::Failed
+            };
+            set_status(candidate.hash, status, receipt.block_number)?;
+
+            // The nonce is spent, so any other same-nonce transaction can never land.
+            for other in chain.iter().filter(|tx| tx.hash != candidate.hash) {
+                if other.status == TxStatus::Pending {
+                    set_status(other.hash, TxStatus::Replaced, None)?;
+                }
+            }
+            return Ok(Some(receipt));
+        }
+    }
+    Ok(None)
+}
+
+/// Receipt of `hash` or of the replacement that used its nonce, and whether that one was
+/// a cancellation. Hashes the tracker doesn't know are looked up as is.
+pub async fn included(
+    provider: &RootProvider,
+    hash: TxHash,
+) -> Result<Option<(TransactionReceipt, bool)>, anyhow::Error> {
+    if get(hash)?.is_none() {
+        let receipt = provider.get_transaction_receipt(hash).await?;
+        return Ok(receipt.map(|receipt| (receipt, false)));
+    }
+    let Some(receipt) = check_inclusion(provider, hash).await? else {
+        return Ok(None);
+    };
+    let cancelled = get(receipt.transaction_hash)?.is_some_and(|tx| tx.is_cancellation());
+    Ok(Some((receipt, cancelled)))
+}
+
+/// Wait until the transaction, or one of its replacements, is included.
+pub async fn wait_for_receipt(
+    provider: &RootProvider,
+    hash: TxHash,
+) -> Result<TransactionReceipt, anyhow::Error> {
+    let started = tokio::time::Instant::now();
+    loop {
+        match check_inclusion(provider, hash).await {
+            Ok(Some(receipt)) => return Ok(receipt),
+            Ok(None) => {}
+            Err(e) => {
+                let reason = format!("its receipt lookup failed: {}", e);
+                return Err(Unconfirmed::new(hash, reason).into());
+            }
+        }
+        if started.elapsed() >= *TX_WAIT_TIMEOUT {
+            let reason = format!(
+                "is still pending after {}s, see /txs/pending",
+                TX_WAIT_TIMEOUT.as_secs()
+            );
+            return Err(Unconfirmed::new(hash, reason).into());
+        }
+        tokio::time::sleep(*TX_POLL_INTERVAL).await;
+    }
+}
+
+fn bump_fee(fee: u128) -> u128 {
+    fee + (fee * *TX_FEE_BUMP_PERCENT / 100).max(1)
+}
+
+/// Re-sign `request` with the stuck transaction's nonce and bumped fees.
+async fn replace(
+    tx: &TrackedTx,
+    request: TransactionRequest,
+    tool: String,
+) -> Result<TxHash, anyhow::Error> {
+    if tx.status != TxStatus::Pending {
+        return Err(anyhow!("transaction {} is not pending", tx.hash));
+    }
+    if !tx.replaceable {
+        return Err(anyhow!("transaction {} cannot be replaced", tx.hash));
+    }
+
+    let provider = make_provider(tx.chain.clone())?;
+
+    // Fees may have been filled by the provider, so read them back from the node.
+    let (max_fee, priority_fee) = match (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(priority_fee)) => (max_fee, priority_fee),
+        _ => {
+            let on_chain = provider
+                .get_transaction_by_hash(tx.hash)
+                .await?
+                .ok_or_else(|| anyhow!("transaction {} not found on node", tx.hash))?;
+            (
+                on_chain.max_fee_per_gas(),
+                on_chain.max_priority_fee_per_gas().unwrap_or_default(),
+            )
+        }
+    };
+    let gas_price = provider.get_gas_price().await?;
+    let priority_fee = bump_fee(priority_fee);
+    let max_fee = bump_fee(max_fee).max(gas_price + priority_fee);
+    gas::check_cap(&tx.chain, max_fee)?;
+
+    let request = request
+        .with_from(tx.from)
+        .with_nonce(tx.nonce)
+        .with_max_fee_per_gas(max_fee)
+        .with_max_priority_fee_per_gas(priority_fee);
+
+    let wallet = accounts::account(tx.account.as_deref())?.signer.wallet();
+    let wallet_provider = ProviderBuilder::new()
+        .wallet(wallet)
+        .on_provider(provider.clone());
+    let pending = wallet_provider.send_transaction(request.clone()).await?;
+    let new_hash = *pending.tx_hash();
+
+    let mut replacement = TrackedTx::new(
+        new_hash,
+        request,
+        tx.chain_id,
+        &TxContext::new(&tx.chain, &tool).with_account(tx.account.clone()),
+        true,
+    );
+    replacement.replaces = Some(tx.hash);
+    replacement.bumps = tx.bumps + 1;
+    save(&replacement)?;
+
+    let mut old = tx.clone();
+    old.status = TxStatus::Replaced;
+    old.replaced_by = Some(new_hash);
+    old.updated_at = chrono::Utc::now().timestamp();
+    save(&old)?;
+
+    log::info!(
+        "replaced tx {} (nonce {}) with {}, max_fee={} priority_fee={}",
+        tx.hash,
+        tx.nonce,
+        new_hash,
+        max_fee,
+        priority_fee
+    );
+    Ok(new_hash)
+}
+
+/// Resend a pending transaction with the same nonce and higher fees.
+pub async fn speed_up(hash: TxHash) -> Result<TxHash, anyhow::Error> {
+    let tx = get(hash)?.ok_or_else(|| anyhow!("unknown transaction {}", hash))?;
+    rpc::pinned(replace(&tx, tx.request.clone(), tx.tool.clone())).await
+}
+
+/// Replace a pending transaction with a zero-value self transfer using the same nonce.
+pub async fn cancel(hash: TxHash) -> Result<TxHash, anyhow::Error> {
+    let tx = get(hash)?.ok_or_else(|| anyhow!("unknown transaction {}", hash))?;
+    let request = TransactionRequest::default()
+        .with_to(tx.from)
+        .with_value(U256::ZERO);
+    let tool = if tx.is_cancellation() {
+        tx.tool.clone()
+    } else {
+        format!("cancel:{}", tx.tool)
+    };
+    rpc::pinned(replace(&tx, request, tool)).await
+}
+
+/// Poll every pending transaction once: record inclusions and bump stuck ones.
+async fn poll_pending() -> Result<(), anyhow::Error> {
+    for tx in list_pending()? {
+        let provider = match make_provider(tx.chain.clone()) {
+            Ok(provider) => provider,
+            Err(e) => {
+                log::error!("tracker: no provider for chain {}: {}", tx.chain, e);
+                continue;
+            }
+        };
+        // One failed lookup must not hold up the other pending txs.
+        match check_inclusion(&provider, tx.hash).await {
+            Ok(Some(_)) => continue,
+            Ok(None) => {}
+            Err(e) => {
+                log::warn!("tracker: receipt lookup of {} failed: {}", tx.hash, e);
+                continue;
+            }
+        }
+        if tx.is_stuck() && tx.replaceable && tx.bumps < MAX_AUTO_BUMPS {
+            if let Err(e) = speed_up(tx.hash).await {
+                log::warn!("tracker: speed_up {} failed: {}", tx.hash, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Start the background task that watches pending transactions.
+pub fn start_tracker() {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = poll_pending().await {
+                log::error!("tracker poll error: {}", e);
+            }
+            tokio::time::sleep(*TX_POLL_INTERVAL).await;
+        }
+    });
+}